@@ -1,7 +1,10 @@
 fn main() {
     println!("cargo::rerun-if-changed=build.rs");
+    println!("cargo::rerun-if-changed=proto");
+    let out_dir = std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap());
     tonic_build::configure()
         .build_server(true)
+        .file_descriptor_set_path(out_dir.join("battlebots_descriptor.bin"))
         .protoc_arg("--experimental_allow_proto3_optional")
         .compile_protos(&["proto/battlebots.proto"], &["proto"])
         .unwrap();
@@ -1,232 +1,903 @@
+use std::any::Any;
 use std::fmt::Display;
 use std::fmt::Formatter;
+use std::io::IsTerminal;
 use std::num::NonZero;
+use std::num::NonZeroU32;
+use std::num::NonZeroU64;
 use std::num::NonZeroUsize;
 use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
 use std::time::Duration;
 use std::time::Instant;
 
 use anyhow::Context;
-use futures::future::try_join_all;
+use anyhow::ensure;
+use clap::ValueEnum;
+use futures::future::join_all;
 use governor::Jitter;
 use governor::Quota;
 use governor::clock::DefaultClock;
 use governor::state::InMemoryState;
 use governor::state::NotKeyed;
 use hdrhistogram::Histogram;
+use rand::Rng;
+use tokio::task::JoinHandle;
 use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
 use tracing::Instrument;
 use tracing::Span;
 use tracing::error;
 use tracing::info;
 use tracing::instrument;
 
+use crate::Arrival;
 use crate::Bench;
-use crate::Workload;
-use crate::client::Client;
+use crate::Distribution;
+use crate::Mode;
+use crate::client::Received;
+use crate::client::Target;
+use crate::client::Timings;
+use crate::client::route::Route;
+
+mod progress;
+mod retry;
+mod warm_up;
+
+use progress::Progress;
+use retry::Retries;
+use warm_up::WarmUp;
 
 #[instrument(skip_all)]
-pub async fn benchmark<C>(client: C, bench: Bench) -> anyhow::Result<Report>
+pub async fn benchmark<T>(target: T, bench: Bench, route: &Route) -> anyhow::Result<Report>
 where
-    C: Client + Clone + Send + 'static,
+    T: Target + Clone + Send + 'static,
 {
+    let pacing = pacing(&bench)?;
+    let ct_progress = CancellationToken::new();
+    let (progress, display) = show_progress(&bench, ct_progress.clone());
+    let warm_up = Duration::from_secs(bench.warm_up);
+    let warm_up_policy = warm_up_policy(&bench, warm_up);
     let Bench {
-        workload,
         workers,
-        rate,
+        mode,
         duration,
-        jitter,
+        requests,
         continue_on_error,
-        warm_up,
+        max_attempts,
+        retry_backoff,
+        retry_backoff_max,
+        retry_status,
+        retry_code,
+        ..
     } = bench;
-    let jitter = Duration::from_micros(jitter);
-    let duration = Duration::from_secs(duration);
-    let warm_up = Duration::from_secs(warm_up);
-
-    let rate_limiter = Arc::new(governor::RateLimiter::direct(
-        Quota::per_second(rate).allow_burst(NonZero::new(1).unwrap()),
-    ));
+    let duration = duration.map(Duration::from_secs);
+    let retry = Arc::new(retry::Policy {
+        max_attempts,
+        backoff: Duration::from_millis(retry_backoff),
+        max_backoff: Duration::from_millis(retry_backoff_max),
+        statuses: retry_status,
+        codes: retry_code.into_iter().map(tonic::Code::from).collect(),
+    });
     let ct = CancellationToken::new();
     let ct_warm_up = CancellationToken::new();
-
-    info!("Warming up for {:.1} s", warm_up.as_secs_f64());
-    let workers: Vec<_> = (client, rate_limiter)
+    let shared = Shared {
+        ct: ct.clone(),
+        ct_warm_up: ct_warm_up.clone(),
+        warm_up: Arc::new(WarmUp::new(warm_up_policy)),
+        countdown: requests.map(|requests| Arc::new(Countdown::new(requests))),
+        progress: progress.clone(),
+        retry,
+        seq: Arc::default(),
+    };
+    let warm_up_begin = Instant::now();
+    let worker_count = workers;
+    let tracker = TaskTracker::new();
+    let workers: Vec<_> = target
         .multiply(workers)
-        .map(|(client, rate_limiter)| {
-            tokio::spawn(
-                work(
-                    workload,
-                    ct.clone(),
-                    client,
-                    rate_limiter,
-                    jitter,
-                    continue_on_error,
-                    ct_warm_up.clone(),
-                )
-                .instrument(Span::current()),
+        .map(|target| {
+            tracker.spawn(
+                work(target, pacing(), continue_on_error, shared.clone())
+                    .instrument(Span::current()),
             )
         })
         .collect();
-    let cancelled = tokio::select! {
+    tracker.close();
+    let mut incomplete = Vec::new();
+    let mut warm_up_reason = None;
+    tokio::select! {
         () = ct.cancelled() => {
             info!("Cancelled by worker");
-            true
+            incomplete.push("cancelled by a worker error during warm-up".to_string());
         },
-        () = tokio::time::sleep(warm_up) => {
-            info!("Completed warm up");
-            false
+        reason = shared.warm_up.done(warm_up) => {
+            info!("Completed warm up {reason}");
+            warm_up_reason = Some(reason);
         },
         result = tokio::signal::ctrl_c() => {
             result.expect("failed to listen for ctrl-c");
             info!("Cancelled by user");
-            true
+            incomplete.push("interrupted during warm-up".to_string());
         },
-    };
+    }
     ct_warm_up.cancel();
-    let mut begin = Instant::now();
-    if !cancelled {
-        info!("Benchmarking for {:.1} s", duration.as_secs_f64());
-        begin = Instant::now();
-        tokio::select! {
-            () = ct.cancelled() => {
-                info!("Cancelled by worker");
-            },
-            () = tokio::time::sleep(duration) => {
-                info!("Finished");
-            },
-            result = tokio::signal::ctrl_c() => {
-                result.expect("failed to listen for ctrl-c");
-                info!("Cancelled by user");
-            },
-        }
+    let warm_up_elapsed = warm_up_begin.elapsed();
+    if let Some(progress) = &progress {
+        progress.start_measuring();
     }
+    let begin = if incomplete.is_empty() {
+        measure(
+            &ct,
+            &tracker,
+            duration,
+            shared.countdown.as_deref(),
+            &mut incomplete,
+        )
+        .await
+    } else {
+        Instant::now()
+    };
     ct.cancel();
-    let work_reports = try_join_all(workers).await.context("joining workers")?;
+    let work_reports = join_workers(workers, &mut incomplete).await;
     let elapsed = begin.elapsed();
+    ct_progress.cancel();
+    if let Some(display) = display {
+        display.await.context("joining progress display")?;
+    }
 
-    Ok(Report::new(work_reports, elapsed))
+    Ok(Report::new(
+        work_reports,
+        elapsed,
+        (warm_up_elapsed, warm_up_reason),
+        Setup {
+            mode,
+            workers: worker_count,
+            route: route.to_string(),
+            transport: route.transport(),
+            retrying: max_attempts.get() > 1,
+        },
+        incomplete,
+    ))
 }
 
-#[instrument(skip_all)]
-async fn work<C>(
-    workload: Workload,
-    ct: CancellationToken,
-    mut client: C,
-    rate_limiter: Arc<governor::RateLimiter<NotKeyed, InMemoryState, DefaultClock>>,
-    jitter: Duration,
-    continue_on_error: bool,
-    ct_warm_up: CancellationToken,
-) -> WorkReport
-where
-    C: Client,
-{
-    let jitter = Jitter::up_to(jitter);
-    let mut histogram = Histogram::new(3).unwrap();
+/// Measure until `duration` passed, `countdown` ran out or all `workers`
+/// exited, noting in `incomplete` if a worker or the user cut it short.
+/// Returns when measuring began.
+async fn measure(
+    ct: &CancellationToken,
+    workers: &TaskTracker,
+    duration: Option<Duration>,
+    countdown: Option<&Countdown>,
+    incomplete: &mut Vec<String>,
+) -> Instant {
+    match (duration, countdown) {
+        (Some(duration), Some(countdown)) => info!(
+            "Benchmarking for {} requests, at most {:.1} s",
+            countdown.requests,
+            duration.as_secs_f64()
+        ),
+        (Some(duration), None) => info!("Benchmarking for {:.1} s", duration.as_secs_f64()),
+        (None, Some(countdown)) => info!("Benchmarking for {} requests", countdown.requests),
+        (None, None) => unreachable!("--duration or --requests is required"),
+    }
     let begin = Instant::now();
-    let mut errors = 0;
-
-    loop {
-        tokio::select! {
-            () = ct_warm_up.cancelled() => { break; },
-            () = rate_limiter.until_ready_with_jitter(jitter) => {},
-        }
-        let result = match workload {
-            Workload::Inty => client.inty().await.map(|_response| ()),
-            Workload::Stringy => client.stringy().await.map(|_response| ()),
-            Workload::Mixed => client.mixed().await.map(|_response| ()),
-        };
-        match result {
-            Ok(()) => {}
+    tokio::select! {
+        () = ct.cancelled() => {
+            info!("Cancelled by worker");
+            incomplete.push(format!(
+                "cancelled by a worker error after {:.1} s",
+                begin.elapsed().as_secs_f64()
+            ));
+        },
+        () = sleep(duration) => {
+            info!("Finished");
+        },
+        () = Countdown::done(countdown) => {
+            info!("Finished");
+        },
+        // Workers that panicked never finish their share of the countdown
+        () = workers.wait() => {
+            info!("All workers exited");
+        },
+        result = tokio::signal::ctrl_c() => {
+            result.expect("failed to listen for ctrl-c");
+            info!("Cancelled by user");
+            incomplete.push(format!(
+                "interrupted after {:.1} s",
+                begin.elapsed().as_secs_f64()
+            ));
+        },
+    }
+    begin
+}
+
+/// How to pace each worker, with open mode's pacing shared by all of them.
+fn pacing(bench: &Bench) -> anyhow::Result<impl Fn() -> Pacing> {
+    let jitter = Jitter::up_to(Duration::from_micros(bench.jitter));
+    let think_time = ThinkTime {
+        distribution: bench.think_time_distribution,
+        mean: Duration::from_millis(bench.think_time),
+    };
+    let rate = match bench.mode {
+        Mode::Open | Mode::PerWorker => Some(
+            bench
+                .rate
+                .context("--rate is required unless in closed mode")?,
+        ),
+        Mode::Closed => {
+            ensure!(bench.rate.is_none(), "--rate doesn't apply in closed mode");
+            None
+        }
+    };
+    let arrival = bench.arrival;
+    let burst_on = Duration::from_millis(bench.burst_on.get());
+    let burst_off = Duration::from_millis(bench.burst_off);
+    let open = move |rate| match arrival {
+        Arrival::Uniform => Pacing::Limited {
+            rate_limiter: rate_limiter(rate),
+            jitter,
+        },
+        Arrival::Poisson | Arrival::Bursty => {
+            Pacing::Scheduled(Arc::new(Schedule::new(arrival, rate, burst_on, burst_off)))
+        }
+    };
+    let shared = rate.filter(|_| bench.mode == Mode::Open).map(open);
+    Ok(move || match (&shared, rate) {
+        (Some(pacing), _) => pacing.clone(),
+        (None, Some(rate)) => open(rate),
+        (None, None) => Pacing::Closed { think_time },
+    })
+}
+
+/// Show live progress until `ct_progress` is cancelled, if stderr is a
+/// terminal and it isn't turned off.
+fn show_progress(
+    bench: &Bench,
+    ct_progress: CancellationToken,
+) -> (Option<Arc<Progress>>, Option<JoinHandle<()>>) {
+    let progress =
+        (!bench.no_progress && std::io::stderr().is_terminal()).then(Arc::<Progress>::default);
+    let rate = match bench.mode {
+        Mode::Open => bench.rate.map(NonZero::get),
+        Mode::PerWorker => {
+            let workers = u32::try_from(bench.workers.get()).unwrap_or(u32::MAX);
+            bench.rate.map(|rate| rate.get().saturating_mul(workers))
+        }
+        Mode::Closed => None,
+    };
+    let duration = bench.duration.map(Duration::from_secs);
+    let display = progress.clone().map(|progress| {
+        tokio::spawn(async move { progress.display(rate, duration, ct_progress).await })
+    });
+    (progress, display)
+}
+
+/// When to end the warm-up, taking at most `warm_up`.
+fn warm_up_policy(bench: &Bench, warm_up: Duration) -> warm_up::Policy {
+    match (bench.warm_up_requests, bench.warm_up_stable) {
+        (Some(requests), _) => {
+            info!(
+                "Warming up for {requests} requests, at most {:.1} s",
+                warm_up.as_secs_f64()
+            );
+            warm_up::Policy::Requests(requests)
+        }
+        (None, Some(tolerance)) => {
+            info!(
+                "Warming up until p50 is stable within {tolerance}%, at most {:.1} s",
+                warm_up.as_secs_f64()
+            );
+            warm_up::Policy::Stable {
+                tolerance,
+                windows: bench.warm_up_windows,
+                window: Duration::from_millis(bench.warm_up_window.get()),
+            }
+        }
+        (None, None) => {
+            info!("Warming up for {:.1} s", warm_up.as_secs_f64());
+            warm_up::Policy::Time
+        }
+    }
+}
+
+/// Wait for all workers, noting any that failed in `incomplete`.
+async fn join_workers(
+    workers: Vec<JoinHandle<WorkReport>>,
+    incomplete: &mut Vec<String>,
+) -> Vec<WorkReport> {
+    let mut work_reports = Vec::with_capacity(workers.len());
+    for (worker, joined) in join_all(workers).await.into_iter().enumerate() {
+        match joined {
+            Ok(work_report) => work_reports.push(work_report),
             Err(error) => {
-                errors += 1;
-                if !continue_on_error {
-                    error!(%error, error_dbg=?error, "error during warm-up");
-                    ct.cancel();
-                }
+                let reason = if error.is_panic() {
+                    panic_message(error.into_panic().as_ref())
+                } else {
+                    error.to_string()
+                };
+                error!(worker, reason, "Worker failed, its data is lost");
+                incomplete.push(format!("worker {worker} failed: {reason}"));
             }
         }
     }
+    work_reports
+}
+
+type RateLimiter = governor::RateLimiter<NotKeyed, InMemoryState, DefaultClock>;
+
+fn rate_limiter(rate: NonZeroU32) -> Arc<RateLimiter> {
+    Arc::new(governor::RateLimiter::direct(
+        Quota::per_second(rate).allow_burst(NonZero::new(1).unwrap()),
+    ))
+}
+
+/// When a worker sends its next request.
+#[derive(Clone)]
+enum Pacing {
+    /// Whenever the rate limiter allows, which may be shared with other
+    /// workers
+    Limited {
+        rate_limiter: Arc<RateLimiter>,
+        jitter: Jitter,
+    },
+    /// At the next arrival time of the schedule, which may be shared with
+    /// other workers
+    Scheduled(Arc<Schedule>),
+    /// As soon as the previous response arrived, after thinking
+    Closed { think_time: ThinkTime },
+}
 
-    loop {
-        tokio::select! {
-            () = ct.cancelled() => { break; },
-            () = rate_limiter.until_ready_with_jitter(jitter) => {},
+impl Pacing {
+    /// Wait until the next request may be sent, returning when it was due
+    /// if it has an arrival time.
+    ///
+    /// Think time only goes between requests, so there is none before a
+    /// worker's `first` one.
+    async fn wait(&self, first: bool) -> Option<Instant> {
+        match self {
+            Self::Limited {
+                rate_limiter,
+                jitter,
+            } => rate_limiter.until_ready_with_jitter(*jitter).await,
+            Self::Scheduled(schedule) => {
+                let arrival = schedule.next();
+                tokio::time::sleep_until(arrival.into()).await;
+                return Some(arrival);
+            }
+            Self::Closed { .. } if first => {}
+            Self::Closed { think_time } => {
+                let think_time = think_time.sample(&mut rand::rng());
+                if !think_time.is_zero() {
+                    tokio::time::sleep(think_time).await;
+                }
+            }
         }
+        None
+    }
+}
+
+/// Arrival times of requests, handed out in order to whichever worker asks
+/// next.
+struct Schedule {
+    arrival: Arrival,
+    /// Requests per second, on average
+    rate: f64,
+    burst_on: Duration,
+    burst_off: Duration,
+    begin: Instant,
+    next: Mutex<Instant>,
+}
+
+impl Schedule {
+    fn new(arrival: Arrival, rate: NonZeroU32, burst_on: Duration, burst_off: Duration) -> Self {
         let begin = Instant::now();
-        let result = match workload {
-            Workload::Inty => client.inty().await.map(|_response| ()),
-            Workload::Stringy => client.stringy().await.map(|_response| ()),
-            Workload::Mixed => client.mixed().await.map(|_response| ()),
-        };
-        let elapsed = u64::try_from(begin.elapsed().as_micros()).expect("gosh");
-        match result {
-            Ok(()) => {
-                histogram.record(elapsed).unwrap();
+        Self {
+            arrival,
+            rate: f64::from(rate.get()),
+            burst_on,
+            burst_off,
+            begin,
+            next: Mutex::new(begin),
+        }
+    }
+
+    /// Take the next arrival time.
+    fn next(&self) -> Instant {
+        let mut next = self.next.lock().unwrap();
+        let arrival = *next;
+        *next = self.after(arrival, &mut rand::rng());
+        arrival
+    }
+
+    /// The arrival following the one at `arrival`.
+    fn after(&self, arrival: Instant, rng: &mut impl Rng) -> Instant {
+        match self.arrival {
+            Arrival::Uniform => arrival + Duration::from_secs_f64(1.0 / self.rate),
+            Arrival::Poisson => {
+                let uniform: f64 = rng.random();
+                arrival + Duration::from_secs_f64(-(1.0 - uniform).ln() / self.rate)
             }
-            Err(error) => {
-                errors += 1;
+            Arrival::Bursty => {
+                // Arrive faster while on, so that the rate is kept on average
+                let on = self.burst_on.as_secs_f64();
+                let period = on + self.burst_off.as_secs_f64();
+                let next = arrival + Duration::from_secs_f64(on / period / self.rate);
+                let position = (next - self.begin).as_secs_f64() % period;
+                if position < on {
+                    next
+                } else {
+                    next + Duration::from_secs_f64(period - position)
+                }
+            }
+        }
+    }
+}
+
+/// How long a worker waits between requests in closed mode.
+#[derive(Clone, Copy)]
+struct ThinkTime {
+    distribution: Distribution,
+    mean: Duration,
+}
+
+impl ThinkTime {
+    fn sample(&self, rng: &mut impl Rng) -> Duration {
+        match self.distribution {
+            Distribution::Constant => self.mean,
+            Distribution::Uniform => self.mean.mul_f64(rng.random_range(0.0..2.0)),
+            Distribution::Exponential => {
+                let uniform: f64 = rng.random();
+                self.mean.mul_f64(-(1.0 - uniform).ln())
+            }
+        }
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        (*message).to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "panicked".to_string()
+    }
+}
+
+/// Sleep for `duration`, or forever if there is none.
+async fn sleep(duration: Option<Duration>) {
+    match duration {
+        Some(duration) => tokio::time::sleep(duration).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// What all workers of a benchmark share.
+#[derive(Clone)]
+struct Shared {
+    ct: CancellationToken,
+    ct_warm_up: CancellationToken,
+    warm_up: Arc<WarmUp>,
+    countdown: Option<Arc<Countdown>>,
+    progress: Option<Arc<Progress>>,
+    retry: Arc<retry::Policy>,
+    /// Numbers the requests of all workers
+    seq: Arc<AtomicU64>,
+}
+
+/// Hands out a fixed number of requests to measure across all workers.
+struct Countdown {
+    requests: u64,
+    unclaimed: AtomicU64,
+    completed: AtomicU64,
+    done: CancellationToken,
+}
+
+impl Countdown {
+    fn new(requests: NonZeroU64) -> Self {
+        Self {
+            requests: requests.get(),
+            unclaimed: AtomicU64::new(requests.get()),
+            completed: AtomicU64::new(0),
+            done: CancellationToken::new(),
+        }
+    }
+
+    /// Claim a request to send, unless all of them have been claimed. The
+    /// request counts as completed once the claim is dropped, even by a
+    /// worker panicking.
+    fn claim(&self) -> Option<Claim<'_>> {
+        self.unclaimed
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |unclaimed| {
+                unclaimed.checked_sub(1)
+            })
+            .ok()
+            .map(|_| Claim(self))
+    }
+
+    /// Count a claimed request as completed.
+    fn complete(&self) {
+        if self.completed.fetch_add(1, Ordering::Relaxed) + 1 == self.requests {
+            self.done.cancel();
+        }
+    }
+
+    /// Wait until all requests completed, or forever if there is no
+    /// countdown.
+    async fn done(countdown: Option<&Self>) {
+        match countdown {
+            Some(countdown) => countdown.done.cancelled().await,
+            None => std::future::pending().await,
+        }
+    }
+}
+
+/// A request claimed from a [`Countdown`], completed when dropped.
+struct Claim<'a>(&'a Countdown);
+
+impl Drop for Claim<'_> {
+    fn drop(&mut self) {
+        self.0.complete();
+    }
+}
+
+#[instrument(skip_all)]
+async fn work<T>(
+    mut target: T,
+    pacing: Pacing,
+    continue_on_error: bool,
+    shared: Shared,
+) -> WorkReport
+where
+    T: Target,
+{
+    let Shared {
+        ct,
+        ct_warm_up,
+        warm_up: shared_warm_up,
+        countdown,
+        progress,
+        retry,
+        seq,
+    } = shared;
+    let begin = Instant::now();
+    let mut warm_up = Observed::default();
+    let mut measured = Observed::default();
+    let mut first = true;
+
+    for (phase, ct_phase, warming_up) in [
+        (&mut warm_up, &ct_warm_up, true),
+        (&mut measured, &ct, false),
+    ] {
+        let countdown = countdown.as_deref().filter(|_| !warming_up);
+        loop {
+            let scheduled = tokio::select! {
+                () = ct_phase.cancelled() => { break; },
+                scheduled = pacing.wait(first) => scheduled,
+            };
+            first = false;
+            let claim = match countdown.map(Countdown::claim) {
+                Some(None) => break,
+                claim => claim.flatten(),
+            };
+            let tracked = progress.as_deref().map(Progress::track);
+            let begin = Instant::now();
+            // Latency counts from when the request was due, so that falling
+            // behind the schedule isn't hidden
+            let due = scheduled.map_or(begin, |scheduled| scheduled.min(begin));
+            let seq = seq.fetch_add(1, Ordering::Relaxed);
+            let mut result = target.request(seq).await;
+            let first_attempt = micros(begin.elapsed());
+            let mut attempts = 1;
+            while let Err(error) = &result {
+                if !retry.retries(T::failure(error), attempts) {
+                    break;
+                }
+                let backoff = retry.backoff(attempts, &mut rand::rng());
+                tokio::time::sleep(backoff).await;
+                attempts += 1;
+                result = target.request(seq).await;
+            }
+            let elapsed = micros(due.elapsed());
+            // Validating is no part of the request, so it isn't timed
+            let result = result.and_then(|received| {
+                target.check(&received.response)?;
+                Ok(Received {
+                    timings: Timings {
+                        queued: scheduled.map(|_| begin - due),
+                        ..received.timings
+                    },
+                    ..received
+                })
+            });
+            phase
+                .retries
+                .record(first_attempt, attempts, result.is_ok());
+            let latency = result.is_ok().then_some(elapsed);
+            if let Some(tracked) = tracked {
+                tracked.finish(latency);
+            }
+            if warming_up {
+                shared_warm_up.record(latency);
+            }
+            let observed = phase.observe::<T>(result, elapsed);
+            drop(claim);
+            if let Err(error) = observed {
                 if !continue_on_error {
-                    error!(%error, error_dbg=?error);
+                    if warming_up {
+                        error!(%error, error_dbg=?error, "error during warm-up");
+                    } else {
+                        error!(%error, error_dbg=?error);
+                    }
                     ct.cancel();
                 }
             }
         }
     }
     WorkReport {
-        histogram,
-        errors,
+        warm_up,
+        measured,
         _duration: begin.elapsed(),
     }
 }
 
+fn micros(duration: Duration) -> u64 {
+    u64::try_from(duration.as_micros()).expect("gosh")
+}
+
 struct WorkReport {
-    histogram: Histogram<u64>,
-    errors: usize,
+    warm_up: Observed,
+    measured: Observed,
     _duration: Duration,
 }
 
-pub struct Report {
+/// What happened to the requests of one or more workers during a phase of
+/// the benchmark.
+struct Observed {
     histogram: Histogram<u64>,
     errors: usize,
+    invalid: usize,
+    sizes: Sizes,
+    breakdown: Breakdown,
+    retries: Retries,
+}
+
+impl Observed {
+    /// Record the result of a request, handing back the error if it failed.
+    fn observe<T: Target>(
+        &mut self,
+        result: Result<Received<T::Response>, T::Error>,
+        elapsed: u64,
+    ) -> Result<(), T::Error> {
+        match result {
+            Ok(received) => {
+                self.histogram.record(elapsed).unwrap();
+                self.sizes
+                    .record(received.body_bytes, received.header_bytes);
+                self.breakdown.record(&received.timings);
+                Ok(())
+            }
+            Err(error) => {
+                if T::is_invalid(&error) {
+                    self.invalid += 1;
+                } else {
+                    self.errors += 1;
+                }
+                Err(error)
+            }
+        }
+    }
+}
+
+impl Default for Observed {
+    fn default() -> Self {
+        Self {
+            histogram: Histogram::new(3).unwrap(),
+            errors: 0,
+            invalid: 0,
+            sizes: Sizes::default(),
+            breakdown: Breakdown::default(),
+            retries: Retries::default(),
+        }
+    }
+}
+
+impl std::ops::AddAssign for Observed {
+    fn add_assign(&mut self, rhs: Self) {
+        self.histogram += rhs.histogram;
+        self.errors += rhs.errors;
+        self.invalid += rhs.invalid;
+        self.sizes += rhs.sizes;
+        self.breakdown += rhs.breakdown;
+        self.retries += rhs.retries;
+    }
+}
+
+pub struct Report {
+    measured: Observed,
     duration: Duration,
+    warm_up: WarmUpSummary,
+    setup: Setup,
+    spread: Spread,
+    /// Why the run did not go as planned, if it did not
+    incomplete: Vec<String>,
 }
 
 impl Report {
-    fn new(work_reports: impl IntoIterator<Item = WorkReport>, duration: Duration) -> Self {
-        let mut histogram = Histogram::new(3).unwrap();
-        let mut errors = 0;
+    fn new(
+        work_reports: impl IntoIterator<Item = WorkReport>,
+        duration: Duration,
+        warm_up: (Duration, Option<String>),
+        setup: Setup,
+        incomplete: Vec<String>,
+    ) -> Self {
+        let mut measured = Observed::default();
+        let mut warm_up_observed = Observed::default();
+        let mut spread = Spread::default();
         for work_report in work_reports {
-            histogram += work_report.histogram;
-            errors += work_report.errors;
+            spread.add(&work_report.measured.histogram);
+            measured += work_report.measured;
+            warm_up_observed += work_report.warm_up;
         }
+        let (warm_up_duration, warm_up_reason) = warm_up;
         Self {
+            measured,
+            duration,
+            warm_up: WarmUpSummary {
+                observed: warm_up_observed,
+                duration: warm_up_duration,
+                reason: warm_up_reason,
+            },
+            setup,
+            spread,
+            incomplete,
+        }
+    }
+}
+
+/// How the benchmark was run.
+struct Setup {
+    mode: Mode,
+    workers: NonZeroUsize,
+    /// Where requests went, and how
+    route: String,
+    transport: &'static str,
+    /// Whether requests may have been retried
+    retrying: bool,
+}
+
+/// What happened during the warm-up, which is not part of the measurements.
+struct WarmUpSummary {
+    observed: Observed,
+    duration: Duration,
+    /// Why the warm-up ended, unless it was interrupted
+    reason: Option<String>,
+}
+
+impl Display for WarmUpSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let Observed {
             histogram,
             errors,
-            duration,
+            invalid,
+            ..
+        } = &self.observed;
+        match &self.reason {
+            Some(reason) => writeln!(f, "Warm-up ({reason}):")?,
+            None => writeln!(f, "Warm-up (interrupted):")?,
         }
+        write!(
+            f,
+            "{}",
+            Latencies {
+                histogram,
+                unit: "us",
+            }
+        )?;
+        let total_requests = usize::try_from(histogram.len()).unwrap() + errors + invalid;
+        writeln!(f, "     Total requests: {total_requests}")?;
+        writeln!(
+            f,
+            "            Elapsed: {:.2} s",
+            self.duration.as_secs_f64()
+        )?;
+        writeln!(f, "    Error responses: {errors}")?;
+        writeln!(f, "  Invalid responses: {invalid}")?;
+        Ok(())
+    }
+}
+
+/// The range of per-worker statistics, to reveal workers being treated
+/// unfairly.
+#[derive(Default)]
+struct Spread {
+    requests: Option<(u64, u64)>,
+    p50: Option<(u64, u64)>,
+    p99: Option<(u64, u64)>,
+}
+
+impl Spread {
+    fn add(&mut self, histogram: &Histogram<u64>) {
+        fn widen(range: &mut Option<(u64, u64)>, value: u64) {
+            let (min, max) = range.get_or_insert((value, value));
+            *min = value.min(*min);
+            *max = value.max(*max);
+        }
+        widen(&mut self.requests, histogram.len());
+        if !histogram.is_empty() {
+            widen(&mut self.p50, histogram.value_at_quantile(0.50));
+            widen(&mut self.p99, histogram.value_at_quantile(0.99));
+        }
+    }
+}
+
+impl Display for Spread {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Per-worker spread:")?;
+        for (name, range, unit) in [
+            ("Requests", self.requests, ""),
+            ("p50", self.p50, " us"),
+            ("p99", self.p99, " us"),
+        ] {
+            match range {
+                Some((min, max)) => writeln!(f, "{name:>19}: {min}{unit} .. {max}{unit}")?,
+                None => writeln!(f, "{name:>19}: none")?,
+            }
+        }
+        Ok(())
     }
 }
 
 impl Display for Report {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "Latencies:")?;
-        let quantiles = [0.50, 0.90, 0.95, 0.99, 1.00];
-        let mut previous_microseconds = self.histogram.min();
-        for quantile in quantiles {
-            let microseconds = self.histogram.value_at_quantile(quantile);
-            let samples_up_to = self.histogram.count_between(0, microseconds);
-            writeln!(
-                f,
-                "\t{:>3}% (n={:>6}) [{:>7} us .. {:>7} us]",
-                quantile * 100.0,
-                samples_up_to,
-                previous_microseconds,
-                microseconds,
-            )?;
-            previous_microseconds = microseconds;
+        if !self.incomplete.is_empty() {
+            writeln!(f, "Incomplete run:")?;
+            for reason in &self.incomplete {
+                writeln!(f, "\t{reason}")?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f, "{}", self.warm_up)?;
+        let Observed {
+            histogram,
+            errors,
+            invalid,
+            sizes,
+            breakdown,
+            retries,
+        } = &self.measured;
+        if self.setup.retrying {
+            writeln!(f, "Latencies, including retries:")?;
+        } else {
+            writeln!(f, "Latencies:")?;
         }
+        write!(
+            f,
+            "{}",
+            Latencies {
+                histogram,
+                unit: "us",
+            }
+        )?;
         writeln!(f)?;
-        let total_requests = usize::try_from(self.histogram.len()).unwrap() + self.errors;
+        if !breakdown.is_empty() {
+            writeln!(f, "{breakdown}")?;
+        }
+        let total_requests = usize::try_from(histogram.len()).unwrap() + errors + invalid;
+        let mode = self
+            .setup
+            .mode
+            .to_possible_value()
+            .expect("no skipped modes");
+        writeln!(
+            f,
+            "               Mode: {}, {} workers",
+            mode.get_name(),
+            self.setup.workers
+        )?;
+        writeln!(f, "              Route: {}", self.setup.route)?;
+        writeln!(f, "          Transport: {}", self.setup.transport)?;
         writeln!(f, "     Total requests: {total_requests}")?;
         writeln!(
             f,
@@ -236,7 +907,195 @@ impl Display for Report {
         #[allow(clippy::cast_precision_loss)]
         let requests_per_second = total_requests as f64 / self.duration.as_secs_f64();
         writeln!(f, "Requests per second: {requests_per_second:.2}")?;
-        writeln!(f, "    Error responses: {}", self.errors)?;
+        writeln!(f, "    Error responses: {errors}")?;
+        writeln!(f, "  Invalid responses: {invalid}")?;
+        writeln!(f)?;
+        if self.setup.retrying {
+            writeln!(f, "{retries}")?;
+        }
+        writeln!(f, "{}", self.spread)?;
+        let Sizes {
+            body: body_bytes,
+            headers: header_bytes,
+            received: total_bytes,
+        } = sizes;
+        writeln!(f, "Response sizes:")?;
+        writeln!(
+            f,
+            "         Body bytes: {:.1} mean, {} p99",
+            body_bytes.mean(),
+            body_bytes.value_at_quantile(0.99)
+        )?;
+        if header_bytes.is_empty() {
+            writeln!(f, "       Header bytes: unknown")?;
+        } else {
+            writeln!(
+                f,
+                "       Header bytes: {:.1} mean, {} p99",
+                header_bytes.mean(),
+                header_bytes.value_at_quantile(0.99)
+            )?;
+        }
+        writeln!(f, "     Bytes received: {total_bytes}")?;
+        #[allow(clippy::cast_precision_loss)]
+        let megabytes_per_second = *total_bytes as f64 / 1e6 / self.duration.as_secs_f64();
+        writeln!(f, "         Throughput: {megabytes_per_second:.2} MB/s")?;
+        Ok(())
+    }
+}
+
+/// Latencies of the parts of successful requests, as far as targets measure
+/// them.
+struct Breakdown {
+    queued: Histogram<u64>,
+    connect: Histogram<u64>,
+    headers: Histogram<u64>,
+    body: Histogram<u64>,
+    decode: Histogram<u64>,
+}
+
+impl Breakdown {
+    fn record(&mut self, timings: &Timings) {
+        let Timings {
+            queued,
+            connect,
+            headers,
+            body,
+            decode,
+        } = timings;
+        for (histogram, duration) in [
+            (&mut self.queued, queued),
+            (&mut self.connect, connect),
+            (&mut self.headers, headers),
+            (&mut self.body, body),
+            (&mut self.decode, decode),
+        ] {
+            if let Some(duration) = duration {
+                histogram.record(micros(*duration)).unwrap();
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.columns().next().is_none()
+    }
+
+    /// The parts that were measured, with their names.
+    fn columns(&self) -> impl Iterator<Item = (&'static str, &Histogram<u64>)> {
+        [
+            ("Queued", &self.queued),
+            ("Connect", &self.connect),
+            ("Headers", &self.headers),
+            ("Body", &self.body),
+            ("Decode", &self.decode),
+        ]
+        .into_iter()
+        .filter(|(_, histogram)| !histogram.is_empty())
+    }
+}
+
+impl Default for Breakdown {
+    fn default() -> Self {
+        Self {
+            queued: Histogram::new(3).unwrap(),
+            connect: Histogram::new(3).unwrap(),
+            headers: Histogram::new(3).unwrap(),
+            body: Histogram::new(3).unwrap(),
+            decode: Histogram::new(3).unwrap(),
+        }
+    }
+}
+
+impl std::ops::AddAssign for Breakdown {
+    fn add_assign(&mut self, rhs: Self) {
+        self.queued += rhs.queued;
+        self.connect += rhs.connect;
+        self.headers += rhs.headers;
+        self.body += rhs.body;
+        self.decode += rhs.decode;
+    }
+}
+
+impl Display for Breakdown {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Latency breakdown (us):")?;
+        write!(f, "\t    ")?;
+        for (name, _) in self.columns() {
+            write!(f, " {name:>9}")?;
+        }
+        writeln!(f)?;
+        for quantile in [0.50, 0.90, 0.95, 0.99, 1.00] {
+            write!(f, "\t{:>3}%", quantile * 100.0)?;
+            for (_, histogram) in self.columns() {
+                write!(f, " {:>9}", histogram.value_at_quantile(quantile))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Sizes of successful responses.
+struct Sizes {
+    body: Histogram<u64>,
+    headers: Histogram<u64>,
+    /// Body and header bytes of all responses
+    received: u64,
+}
+
+impl Sizes {
+    fn record(&mut self, body_bytes: u64, header_bytes: Option<u64>) {
+        self.body.record(body_bytes).unwrap();
+        self.received += body_bytes;
+        if let Some(header_bytes) = header_bytes {
+            self.headers.record(header_bytes).unwrap();
+            self.received += header_bytes;
+        }
+    }
+}
+
+impl Default for Sizes {
+    fn default() -> Self {
+        Self {
+            body: Histogram::new(3).unwrap(),
+            headers: Histogram::new(3).unwrap(),
+            received: 0,
+        }
+    }
+}
+
+impl std::ops::AddAssign for Sizes {
+    fn add_assign(&mut self, rhs: Self) {
+        self.body += rhs.body;
+        self.headers += rhs.headers;
+        self.received += rhs.received;
+    }
+}
+
+/// Quantiles of a latency histogram, one per line.
+pub struct Latencies<'a> {
+    pub histogram: &'a Histogram<u64>,
+    pub unit: &'a str,
+}
+
+impl Display for Latencies<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let Self { histogram, unit } = self;
+        let quantiles = [0.50, 0.90, 0.95, 0.99, 1.00];
+        let mut previous = histogram.min();
+        for quantile in quantiles {
+            let value = histogram.value_at_quantile(quantile);
+            let samples_up_to = histogram.count_between(0, value);
+            writeln!(
+                f,
+                "\t{:>3}% (n={:>6}) [{:>7} {unit} .. {:>7} {unit}]",
+                quantile * 100.0,
+                samples_up_to,
+                previous,
+                value,
+            )?;
+            previous = value;
+        }
         Ok(())
     }
 }
@@ -274,3 +1133,81 @@ impl<T: Clone> Iterator for Multiplied<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::*;
+
+    const RATE: u32 = 1000;
+
+    /// A schedule with bursts of 100 ms every second.
+    fn schedule(arrival: Arrival) -> Schedule {
+        Schedule::new(
+            arrival,
+            NonZeroU32::new(RATE).unwrap(),
+            Duration::from_millis(100),
+            Duration::from_millis(900),
+        )
+    }
+
+    /// The arrivals of `schedule` in its first `horizon`, as offsets from its
+    /// start.
+    fn arrivals(schedule: &Schedule, horizon: Duration) -> Vec<Duration> {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut arrival = schedule.begin;
+        std::iter::from_fn(|| {
+            arrival = schedule.after(arrival, &mut rng);
+            Some(arrival - schedule.begin)
+        })
+        .take_while(|offset| *offset < horizon)
+        .collect()
+    }
+
+    #[test]
+    fn arrivals_are_ordered_and_keep_the_mean_rate() {
+        for arrival in [Arrival::Uniform, Arrival::Poisson, Arrival::Bursty] {
+            let arrivals = arrivals(&schedule(arrival), Duration::from_secs(10));
+            assert!(arrivals.is_sorted(), "{arrival:?}");
+            #[allow(clippy::cast_precision_loss)]
+            let rate = arrivals.len() as f64 / 10.0;
+            assert!(
+                (rate / f64::from(RATE) - 1.0).abs() < 0.03,
+                "{arrival:?}: {rate} per second"
+            );
+        }
+    }
+
+    #[test]
+    fn bursty_arrivals_fall_within_bursts() {
+        for offset in arrivals(&schedule(Arrival::Bursty), Duration::from_secs(5)) {
+            // Arrivals moved to the next burst may round to just before it
+            let position = offset.as_secs_f64() % 1.0;
+            assert!(!(0.1..=1.0 - 1e-6).contains(&position), "{offset:?}");
+        }
+    }
+
+    #[test]
+    fn countdown_completes_once_every_claim_is_dropped() {
+        let countdown = Countdown::new(NonZeroU64::new(3).unwrap());
+        let mut claims: Vec<_> = std::iter::from_fn(|| countdown.claim()).collect();
+        assert_eq!(claims.len(), 3);
+        claims.truncate(1);
+        assert!(!countdown.done.is_cancelled());
+        drop(claims);
+        assert!(countdown.done.is_cancelled());
+    }
+
+    #[test]
+    fn countdown_completes_claims_of_panicking_workers() {
+        let countdown = Countdown::new(NonZeroU64::new(1).unwrap());
+        let worker = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _claim = countdown.claim();
+            panic!("worker panicked");
+        }));
+        assert!(worker.is_err());
+        assert!(countdown.done.is_cancelled());
+    }
+}
@@ -0,0 +1,128 @@
+use std::sync::Mutex;
+use std::sync::OnceLock;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use std::time::Instant;
+
+use hdrhistogram::Histogram;
+use tokio_util::sync::CancellationToken;
+
+/// What the workers have been up to, for the live progress view.
+pub struct Progress {
+    in_flight: AtomicU64,
+    completed: AtomicU64,
+    errors: AtomicU64,
+    /// Latencies in microseconds since the last update
+    latencies: Mutex<Histogram<u64>>,
+    measuring_since: OnceLock<Instant>,
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self {
+            in_flight: AtomicU64::new(0),
+            completed: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            latencies: Mutex::new(Histogram::new(3).unwrap()),
+            measuring_since: OnceLock::new(),
+        }
+    }
+}
+
+impl Progress {
+    /// Count a request as in flight until the returned [`Tracked`] is
+    /// finished or dropped.
+    pub fn track(&self) -> Tracked<'_> {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        Tracked(self)
+    }
+
+    /// Mark the end of the warm-up.
+    pub fn start_measuring(&self) {
+        let _ = self.measuring_since.set(Instant::now());
+    }
+
+    /// Print a status line to stderr every second, until `stop` is
+    /// cancelled.
+    ///
+    /// `rate` is the targeted number of requests per second and `duration`
+    /// how long to measure for, if known.
+    pub async fn display(
+        &self,
+        rate: Option<u32>,
+        duration: Option<Duration>,
+        stop: CancellationToken,
+    ) {
+        let rate = rate.map_or_else(|| "unlimited".to_string(), |rate| rate.to_string());
+        let begin = Instant::now();
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+        interval.tick().await;
+        let mut last = Instant::now();
+        loop {
+            tokio::select! {
+                () = stop.cancelled() => break,
+                _ = interval.tick() => {},
+            }
+            let elapsed = last.elapsed();
+            last = Instant::now();
+            let phase = match self.measuring_since.get() {
+                Some(since) => {
+                    let measured = since.elapsed();
+                    match duration {
+                        Some(duration) => format!(
+                            "Benchmarking {:.0}/{:.0} s ({:.0} s left)",
+                            measured.as_secs_f64(),
+                            duration.as_secs_f64(),
+                            duration.saturating_sub(measured).as_secs_f64()
+                        ),
+                        None => format!("Benchmarking {:.0} s", measured.as_secs_f64()),
+                    }
+                }
+                None => format!("Warming up {:.0} s", begin.elapsed().as_secs_f64()),
+            };
+            let (p50, p99) = {
+                let mut latencies = self.latencies.lock().unwrap();
+                let quantiles = (
+                    latencies.value_at_quantile(0.50),
+                    latencies.value_at_quantile(0.99),
+                );
+                latencies.reset();
+                quantiles
+            };
+            #[allow(clippy::cast_precision_loss)]
+            let requests_per_second =
+                self.completed.swap(0, Ordering::Relaxed) as f64 / elapsed.as_secs_f64();
+            eprint!(
+                "\r\x1b[2K{phase} | {requests_per_second:.0}/{rate} req/s | {} in flight | p50 \
+                 {p50} us, p99 {p99} us | {} errors",
+                self.in_flight.load(Ordering::Relaxed),
+                self.errors.load(Ordering::Relaxed),
+            );
+        }
+        eprintln!();
+    }
+}
+
+#[must_use]
+pub struct Tracked<'a>(&'a Progress);
+
+impl Tracked<'_> {
+    /// Count the request as completed, with a latency in microseconds if it
+    /// succeeded.
+    pub fn finish(self, latency: Option<u64>) {
+        self.0.completed.fetch_add(1, Ordering::Relaxed);
+        match latency {
+            Some(latency) => self.0.latencies.lock().unwrap().record(latency).unwrap(),
+            None => {
+                self.0.errors.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+impl Drop for Tracked<'_> {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
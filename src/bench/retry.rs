@@ -0,0 +1,165 @@
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::num::NonZeroU32;
+use std::time::Duration;
+
+use hdrhistogram::Histogram;
+use rand::Rng;
+use reqwest::StatusCode;
+
+use super::Latencies;
+use crate::client::Failure;
+
+/// Which failed requests to send again, and when.
+pub struct Policy {
+    pub max_attempts: NonZeroU32,
+    /// Backoff before the first retry
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+    pub statuses: Vec<StatusCode>,
+    pub codes: Vec<tonic::Code>,
+}
+
+impl Policy {
+    /// Whether a request that failed with `failure` after `attempts`
+    /// attempts should be sent again.
+    pub fn retries(&self, failure: Failure, attempts: u32) -> bool {
+        attempts < self.max_attempts.get()
+            && match failure {
+                Failure::Connection => true,
+                Failure::Status(status) => self.statuses.contains(&status),
+                Failure::Code(code) => self.codes.contains(&code),
+                Failure::Other => false,
+            }
+    }
+
+    /// How long to wait before retrying after `attempts` attempts, with
+    /// full jitter.
+    pub fn backoff(&self, attempts: u32, rng: &mut impl Rng) -> Duration {
+        let ceiling = self
+            .backoff
+            .saturating_mul(1 << (attempts - 1).min(31))
+            .min(self.max_backoff);
+        ceiling.mul_f64(rng.random())
+    }
+}
+
+/// How often requests had to be sent again.
+pub struct Retries {
+    /// Latencies in microseconds of first attempts, whether they succeeded
+    /// or not
+    first_attempt: Histogram<u64>,
+    /// Attempts after the first
+    attempts: u64,
+    /// Requests that took more than one attempt
+    retried: u64,
+    /// Retried requests that succeeded in the end
+    recovered: u64,
+}
+
+impl Retries {
+    pub fn record(&mut self, first_attempt: u64, attempts: u32, succeeded: bool) {
+        self.first_attempt.record(first_attempt).unwrap();
+        if attempts > 1 {
+            self.attempts += u64::from(attempts - 1);
+            self.retried += 1;
+            if succeeded {
+                self.recovered += 1;
+            }
+        }
+    }
+}
+
+impl Default for Retries {
+    fn default() -> Self {
+        Self {
+            first_attempt: Histogram::new(3).unwrap(),
+            attempts: 0,
+            retried: 0,
+            recovered: 0,
+        }
+    }
+}
+
+impl std::ops::AddAssign for Retries {
+    fn add_assign(&mut self, rhs: Self) {
+        self.first_attempt += rhs.first_attempt;
+        self.attempts += rhs.attempts;
+        self.retried += rhs.retried;
+        self.recovered += rhs.recovered;
+    }
+}
+
+impl Display for Retries {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "First-attempt latencies:")?;
+        write!(
+            f,
+            "{}",
+            Latencies {
+                histogram: &self.first_attempt,
+                unit: "us",
+            }
+        )?;
+        writeln!(f)?;
+        writeln!(f, "     Retry attempts: {}", self.attempts)?;
+        writeln!(
+            f,
+            "   Retried requests: {} ({} recovered)",
+            self.retried, self.recovered
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::*;
+
+    fn policy() -> Policy {
+        Policy {
+            max_attempts: NonZeroU32::new(3).unwrap(),
+            backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+            statuses: vec![StatusCode::SERVICE_UNAVAILABLE],
+            codes: vec![tonic::Code::Unavailable],
+        }
+    }
+
+    #[test]
+    fn retries_retryable_failures_up_to_max_attempts() {
+        let policy = policy();
+        let unavailable = Failure::Status(StatusCode::SERVICE_UNAVAILABLE);
+        assert!(policy.retries(Failure::Connection, 1));
+        assert!(policy.retries(unavailable, 2));
+        assert!(!policy.retries(unavailable, 3));
+        assert!(!policy.retries(Failure::Status(StatusCode::BAD_REQUEST), 1));
+        assert!(policy.retries(Failure::Code(tonic::Code::Unavailable), 1));
+        assert!(!policy.retries(Failure::Code(tonic::Code::Internal), 1));
+        assert!(!policy.retries(Failure::Other, 1));
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_max_with_full_jitter() {
+        let policy = policy();
+        let mut rng = StdRng::seed_from_u64(0);
+        let ceilings = [100, 200, 400, 800, 1000, 1000]
+            .into_iter()
+            .chain(std::iter::repeat_n(1000, 58));
+        for (attempts, ceiling) in (1..).zip(ceilings) {
+            let ceiling = Duration::from_millis(ceiling);
+            let backoffs: Vec<_> = (0..1000)
+                .map(|_| policy.backoff(attempts, &mut rng))
+                .collect();
+            assert!(
+                backoffs.iter().all(|backoff| *backoff <= ceiling),
+                "attempt {attempts}"
+            );
+            assert!(backoffs.iter().any(|backoff| *backoff < ceiling / 10));
+            assert!(backoffs.iter().any(|backoff| *backoff > ceiling * 9 / 10));
+        }
+    }
+}
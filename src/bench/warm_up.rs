@@ -0,0 +1,183 @@
+use std::collections::VecDeque;
+use std::num::NonZeroU64;
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use hdrhistogram::Histogram;
+use tokio::sync::Notify;
+
+/// When the warm-up is over, short of its timeout.
+pub enum Policy {
+    /// Only when the timeout elapsed
+    Time,
+    /// Once this many requests completed, across all workers
+    Requests(NonZeroU64),
+    /// Once the p50 latency of the last `windows` windows stays within
+    /// `tolerance` percent of their lowest
+    Stable {
+        tolerance: f64,
+        windows: NonZeroUsize,
+        window: Duration,
+    },
+}
+
+/// Keeps track of the warm-up across all workers.
+pub struct WarmUp {
+    policy: Policy,
+    requests: AtomicU64,
+    reached: Notify,
+    /// Latencies in microseconds since the last window
+    window: Mutex<Histogram<u64>>,
+}
+
+impl WarmUp {
+    pub fn new(policy: Policy) -> Self {
+        Self {
+            policy,
+            requests: AtomicU64::new(0),
+            reached: Notify::new(),
+            window: Mutex::new(Histogram::new(3).unwrap()),
+        }
+    }
+
+    /// Count a completed request, with a latency in microseconds if it
+    /// succeeded.
+    pub fn record(&self, latency: Option<u64>) {
+        match &self.policy {
+            Policy::Time => {}
+            Policy::Requests(requests) => {
+                if self.requests.fetch_add(1, Ordering::Relaxed) + 1 == requests.get() {
+                    self.reached.notify_one();
+                }
+            }
+            Policy::Stable { .. } => {
+                if let Some(latency) = latency {
+                    self.window.lock().unwrap().record(latency).unwrap();
+                }
+            }
+        }
+    }
+
+    /// Wait until the warm-up is over, at most for `timeout`, returning why
+    /// it is.
+    pub async fn done(&self, timeout: Duration) -> String {
+        let policy = async {
+            match &self.policy {
+                Policy::Time => std::future::pending().await,
+                Policy::Requests(requests) => {
+                    self.reached.notified().await;
+                    format!("after {requests} requests")
+                }
+                Policy::Stable {
+                    tolerance,
+                    windows,
+                    window,
+                } => self.stable(*tolerance, *windows, *window).await,
+            }
+        };
+        tokio::select! {
+            reason = policy => reason,
+            () = tokio::time::sleep(timeout) => match self.policy {
+                Policy::Time => format!("after {:.1} s", timeout.as_secs_f64()),
+                Policy::Requests(_) | Policy::Stable { .. } => {
+                    format!("timed out after {:.1} s", timeout.as_secs_f64())
+                }
+            },
+        }
+    }
+
+    async fn stable(&self, tolerance: f64, windows: NonZeroUsize, window: Duration) -> String {
+        let mut interval = tokio::time::interval(window);
+        interval.tick().await;
+        let mut stability = Stability::new(tolerance, windows);
+        loop {
+            interval.tick().await;
+            let p50 = {
+                let mut latencies = self.window.lock().unwrap();
+                let p50 = (!latencies.is_empty()).then(|| latencies.value_at_quantile(0.50));
+                latencies.reset();
+                p50
+            };
+            if stability.push(p50) {
+                return format!("p50 stable within {tolerance}% over {windows} windows");
+            }
+        }
+    }
+}
+
+/// Whether the p50 latencies of the last windows that had any are stable.
+struct Stability {
+    tolerance: f64,
+    windows: NonZeroUsize,
+    p50s: VecDeque<u64>,
+}
+
+impl Stability {
+    fn new(tolerance: f64, windows: NonZeroUsize) -> Self {
+        Self {
+            tolerance,
+            windows,
+            p50s: VecDeque::with_capacity(windows.get()),
+        }
+    }
+
+    /// Add the p50 of the latest window, if it had any latencies, returning
+    /// whether the last `windows` stay within `tolerance` percent of their
+    /// lowest.
+    #[allow(clippy::cast_precision_loss)]
+    fn push(&mut self, p50: Option<u64>) -> bool {
+        // Windows without any latencies, like those between bursts, say
+        // nothing about stability either way
+        let Some(p50) = p50 else {
+            return false;
+        };
+        if self.p50s.len() == self.windows.get() {
+            self.p50s.pop_front();
+        }
+        self.p50s.push_back(p50);
+        if self.p50s.len() < self.windows.get() {
+            return false;
+        }
+        let min = *self.p50s.iter().min().expect("windows is non-zero");
+        let max = *self.p50s.iter().max().expect("windows is non-zero");
+        (max - min) as f64 <= min as f64 * self.tolerance / 100.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stability(windows: usize) -> Stability {
+        Stability::new(10.0, NonZeroUsize::new(windows).unwrap())
+    }
+
+    #[test]
+    fn stable_once_enough_windows_are_within_tolerance() {
+        let mut stability = stability(3);
+        assert!(!stability.push(Some(1000)));
+        assert!(!stability.push(Some(1050)));
+        assert!(stability.push(Some(1100)));
+    }
+
+    #[test]
+    fn unstable_until_an_outlier_slides_out() {
+        let mut stability = stability(3);
+        for p50 in [1000, 2000, 1000, 1000] {
+            assert!(!stability.push(Some(p50)), "{p50}");
+        }
+        assert!(stability.push(Some(1000)));
+    }
+
+    #[test]
+    fn empty_windows_keep_the_history() {
+        let mut stability = stability(2);
+        assert!(!stability.push(Some(1000)));
+        assert!(!stability.push(None));
+        assert!(!stability.push(None));
+        assert!(stability.push(Some(1000)));
+    }
+}
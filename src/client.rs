@@ -1,15 +1,270 @@
+use std::time::Duration;
+
 use axum::async_trait;
+use axum::http::HeaderMap;
+use axum::http::StatusCode;
+
+use crate::Workload;
+use crate::workloads::Invalid;
+use crate::workloads::Rand;
+use crate::workloads::Validate;
+use crate::workloads::generate;
+use crate::workloads::inty;
+use crate::workloads::mixed;
+use crate::workloads::stringy;
 
+pub mod connect;
+pub mod dynamic;
 pub mod grpc;
+pub mod http;
+mod quic;
 pub mod rest;
+pub mod route;
+pub mod websocket;
 
+/// A client for the built-in workloads.
+///
+/// With a `seed`, the server is asked for the payload generated from it.
 #[async_trait]
 pub trait Client {
-    type Stringy;
-    type Inty: Send;
-    type Mixed;
+    type Stringy: Decoded<Payload = stringy::Payload> + Send;
+    type Inty: Decoded<Payload = inty::Payload> + Send;
+    type Mixed: Decoded<Payload = mixed::Payload> + Send;
     type Error: std::error::Error + Send;
-    async fn stringy(&mut self) -> Result<Self::Stringy, Self::Error>;
-    async fn inty(&mut self) -> Result<Self::Inty, Self::Error>;
-    async fn mixed(&mut self) -> Result<Self::Mixed, Self::Error>;
+    async fn stringy(&mut self, seed: Option<u64>) -> Result<Received<Self::Stringy>, Self::Error>;
+    async fn inty(&mut self, seed: Option<u64>) -> Result<Received<Self::Inty>, Self::Error>;
+    async fn mixed(&mut self, seed: Option<u64>) -> Result<Received<Self::Mixed>, Self::Error>;
+
+    /// What `error` ran into, to decide whether to retry.
+    fn failure(_error: &Self::Error) -> Failure {
+        Failure::Other
+    }
+}
+
+/// A response, along with how large it was when received.
+pub struct Received<T> {
+    pub response: T,
+    /// Size of the encoded body, excluding any framing
+    pub body_bytes: u64,
+    /// Size of the headers before compression, if known
+    pub header_bytes: Option<u64>,
+    pub timings: Timings,
+}
+
+/// How long parts of a request took, where measured.
+///
+/// Apart from `queued` and `connect`, these are measured from sending the
+/// request.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Timings {
+    /// Waiting to be sent after the request was due, when it had an arrival
+    /// time
+    pub queued: Option<Duration>,
+    /// Opening a new connection, including the HTTP/2 handshake
+    pub connect: Option<Duration>,
+    /// Until the response headers arrived
+    pub headers: Option<Duration>,
+    /// Until the last byte of the response body arrived
+    pub body: Option<Duration>,
+    /// Decoding the response body, on its own
+    pub decode: Option<Duration>,
+}
+
+impl<T> Received<T> {
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Received<U> {
+        Received {
+            response: f(self.response),
+            body_bytes: self.body_bytes,
+            header_bytes: self.header_bytes,
+            timings: self.timings,
+        }
+    }
+}
+
+/// Size of `headers`, counting names and values.
+pub fn header_bytes(headers: &HeaderMap) -> u64 {
+    headers
+        .iter()
+        .map(|(name, value)| (name.as_str().len() + value.len()) as u64)
+        .sum()
+}
+
+/// A decoded response, which may lack parts of the payload it represents.
+pub trait Decoded {
+    type Payload;
+    fn payload(&self) -> Result<Self::Payload, Invalid>;
+}
+
+/// Something that can be benchmarked by sending the same kind of request
+/// over and over again.
+#[async_trait]
+pub trait Target {
+    type Response: Send;
+    type Error: std::error::Error + Send;
+
+    /// Send the `seq`th request of the benchmark. Retries send it again with
+    /// the same `seq`.
+    async fn request(&mut self, seq: u64) -> Result<Received<Self::Response>, Self::Error>;
+
+    /// Check a response that arrived, apart from the time measured for the
+    /// request.
+    fn check(&self, _response: &Self::Response) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Whether `error` means that a response arrived but was invalid, rather
+    /// than that the request failed.
+    fn is_invalid(_error: &Self::Error) -> bool {
+        false
+    }
+
+    /// What `error` ran into, to decide whether to retry.
+    fn failure(_error: &Self::Error) -> Failure {
+        Failure::Other
+    }
+}
+
+/// What a failed request ran into, as far as retrying it is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    /// Connecting or sending the request failed
+    Connection,
+    /// An HTTP response with an unexpected status
+    Status(StatusCode),
+    /// A gRPC status other than OK
+    Code(tonic::Code),
+    /// Anything else, like a response that could not be decoded
+    Other,
+}
+
+impl From<&reqwest::Error> for Failure {
+    fn from(error: &reqwest::Error) -> Self {
+        if let Some(status) = error.status() {
+            Self::Status(status)
+        } else if error.is_connect() || error.is_request() {
+            Self::Connection
+        } else {
+            Self::Other
+        }
+    }
+}
+
+impl From<&tonic::Status> for Failure {
+    fn from(status: &tonic::Status) -> Self {
+        Self::Code(status.code())
+    }
+}
+
+/// A [`Target`] that requests one of the built-in workloads from a
+/// [`Client`].
+#[derive(Clone)]
+pub struct Builtin<C> {
+    client: C,
+    workload: Workload,
+    validate: bool,
+    seed: Option<u64>,
+}
+
+impl<C> Builtin<C> {
+    /// With `validate`, responses are checked against the invariants of the
+    /// workload. With a `seed`, the `n`th request asks for the payload
+    /// generated from `seed + n`, which validation then checks for exactly.
+    pub fn new(client: C, workload: Workload, validate: bool, seed: Option<u64>) -> Self {
+        Self {
+            client,
+            workload,
+            validate,
+            seed,
+        }
+    }
+
+    fn validate<R>(response: &R, seed: Option<u64>) -> Result<(), Invalid>
+    where
+        R: Decoded,
+        R::Payload: Rand + Validate + PartialEq,
+    {
+        let payload = response.payload()?;
+        payload.validate()?;
+        match seed {
+            Some(seed) if payload != generate(Some(seed)) => Err(Invalid::Mismatch(seed)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A response of a [`Builtin`] target, along with the seed it asked for.
+pub struct Response<S, I, M> {
+    payload: Payload<S, I, M>,
+    seed: Option<u64>,
+}
+
+pub enum Payload<S, I, M> {
+    Stringy(S),
+    Inty(I),
+    Mixed(M),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error<E> {
+    #[error(transparent)]
+    Request(E),
+    #[error("invalid response: {0}")]
+    Invalid(#[from] Invalid),
+}
+
+#[async_trait]
+impl<C> Target for Builtin<C>
+where
+    C: Client + Send,
+{
+    type Response = Response<C::Stringy, C::Inty, C::Mixed>;
+    type Error = Error<C::Error>;
+
+    async fn request(&mut self, seq: u64) -> Result<Received<Self::Response>, Self::Error> {
+        let seed = self.seed.map(|seed| seed.wrapping_add(seq));
+        let received = match self.workload {
+            Workload::Inty => self
+                .client
+                .inty(seed)
+                .await
+                .map(|received| received.map(Payload::Inty)),
+            Workload::Stringy => self
+                .client
+                .stringy(seed)
+                .await
+                .map(|received| received.map(Payload::Stringy)),
+            Workload::Mixed => self
+                .client
+                .mixed(seed)
+                .await
+                .map(|received| received.map(Payload::Mixed)),
+        };
+        Ok(received
+            .map_err(Error::Request)?
+            .map(|payload| Response { payload, seed }))
+    }
+
+    fn check(&self, response: &Self::Response) -> Result<(), Self::Error> {
+        if !self.validate {
+            return Ok(());
+        }
+        let seed = response.seed;
+        match &response.payload {
+            Payload::Stringy(stringy) => Self::validate(stringy, seed)?,
+            Payload::Inty(inty) => Self::validate(inty, seed)?,
+            Payload::Mixed(mixed) => Self::validate(mixed, seed)?,
+        }
+        Ok(())
+    }
+
+    fn is_invalid(error: &Self::Error) -> bool {
+        matches!(error, Error::Invalid(_))
+    }
+
+    fn failure(error: &Self::Error) -> Failure {
+        match error {
+            Error::Request(error) => C::failure(error),
+            Error::Invalid(_) => Failure::Other,
+        }
+    }
 }
@@ -0,0 +1,150 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use http_body_util::BodyExt;
+use http_body_util::Empty;
+use hyper_util::rt::TokioExecutor;
+use hyper_util::rt::TokioIo;
+
+use super::Builtin;
+use super::Failure;
+use super::Received;
+use super::Target;
+use super::Timings;
+use super::header_bytes;
+use super::route::Route;
+use crate::Protocol;
+use crate::Workload;
+use crate::workloads::inty;
+use crate::workloads::mixed;
+use crate::workloads::stringy;
+
+/// A client opening a new TCP and HTTP/2 connection for every request, to
+/// measure what setting one up costs.
+#[derive(Clone)]
+pub struct Client {
+    protocol: Protocol,
+    route: Arc<Route>,
+    workload: Workload,
+}
+
+impl Client {
+    pub fn new(protocol: Protocol, route: Route, workload: Workload) -> Self {
+        Self {
+            protocol,
+            route: Arc::new(route),
+            workload,
+        }
+    }
+
+    async fn grpc(&self) -> Result<Received<()>, Error> {
+        let begin = Instant::now();
+        let channel = self.route.channel().await?;
+        let connect = begin.elapsed();
+        // Broken down like the REST request, so the first request is timed
+        let received = Builtin::new(
+            super::grpc::Client::new(channel, Vec::new(), true),
+            self.workload,
+            false,
+            None,
+        )
+        .request(0)
+        .await?;
+        Ok(Received {
+            timings: Timings {
+                connect: Some(connect),
+                ..received.timings
+            },
+            ..received.map(drop)
+        })
+    }
+
+    async fn rest(&self) -> Result<Received<()>, Error> {
+        let begin = Instant::now();
+        let stream = self.route.connect().await?;
+        let (mut sender, connection) =
+            hyper::client::conn::http2::handshake(TokioExecutor::new(), TokioIo::new(stream))
+                .await?;
+        // Runs until `sender` is dropped
+        tokio::spawn(connection);
+        let connect = begin.elapsed();
+
+        let path = match self.workload {
+            Workload::Inty => "/inty",
+            Workload::Stringy => "/stringy",
+            Workload::Mixed => "/mixed",
+        };
+        let request = http::Request::get(format!("{}{path}", self.route.base_url()))
+            .body(Empty::<Bytes>::new())
+            .expect("request is valid");
+        let begin = Instant::now();
+        let response = sender.send_request(request).await?;
+        let headers = begin.elapsed();
+        let status = response.status();
+        if !status.is_success() {
+            return Err(Error::Status(status));
+        }
+        let header_bytes = header_bytes(response.headers());
+        let body = response.into_body().collect().await?.to_bytes();
+        let body_elapsed = begin.elapsed();
+        let decode_begin = Instant::now();
+        match self.workload {
+            Workload::Inty => drop(serde_json::from_slice::<inty::Payload>(&body)?),
+            Workload::Stringy => drop(serde_json::from_slice::<stringy::Payload>(&body)?),
+            Workload::Mixed => drop(serde_json::from_slice::<mixed::Payload>(&body)?),
+        }
+        let decode = decode_begin.elapsed();
+        Ok(Received {
+            response: (),
+            body_bytes: body.len() as u64,
+            header_bytes: Some(header_bytes),
+            timings: Timings {
+                connect: Some(connect),
+                headers: Some(headers),
+                body: Some(body_elapsed),
+                decode: Some(decode),
+                ..Timings::default()
+            },
+        })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("connecting: {0}")]
+    Connect(#[from] std::io::Error),
+    #[error("connecting: {0}")]
+    Transport(#[from] tonic::transport::Error),
+    #[error(transparent)]
+    Http(#[from] hyper::Error),
+    #[error(transparent)]
+    Grpc(#[from] super::Error<tonic::Status>),
+    #[error("unexpected status {0}")]
+    Status(http::StatusCode),
+    #[error("decoding response body: {0}")]
+    Decode(#[from] serde_json::Error),
+}
+
+#[async_trait]
+impl Target for Client {
+    type Response = ();
+    type Error = Error;
+
+    async fn request(&mut self, _seq: u64) -> Result<Received<Self::Response>, Self::Error> {
+        match self.protocol {
+            Protocol::Grpc => self.grpc().await,
+            Protocol::Rest => self.rest().await,
+        }
+    }
+
+    fn failure(error: &Self::Error) -> Failure {
+        match error {
+            Error::Connect(_) | Error::Transport(_) | Error::Http(_) => Failure::Connection,
+            Error::Grpc(super::Error::Request(status)) => status.into(),
+            Error::Grpc(super::Error::Invalid(_)) | Error::Decode(_) => Failure::Other,
+            Error::Status(status) => Failure::Status(*status),
+        }
+    }
+}
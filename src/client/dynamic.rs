@@ -0,0 +1,242 @@
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use anyhow::bail;
+use async_trait::async_trait;
+use prost::Message;
+use prost_reflect::DescriptorPool;
+use prost_reflect::DynamicMessage;
+use prost_reflect::MessageDescriptor;
+use tonic::Status;
+use tonic::codec::Codec;
+use tonic::codec::DecodeBuf;
+use tonic::codec::Decoder;
+use tonic::codec::EncodeBuf;
+use tonic::codec::Encoder;
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::transport::Channel;
+
+use super::Failure;
+use super::Received;
+use super::grpc::Metadata;
+use super::route::Route;
+use crate::template::Template;
+
+/// Load message and service descriptors, either by compiling `.proto` files
+/// or by decoding a serialized `FileDescriptorSet`.
+pub fn load_descriptors(
+    protos: &[PathBuf],
+    includes: &[PathBuf],
+    descriptor_set: Option<&Path>,
+) -> anyhow::Result<DescriptorPool> {
+    if let Some(descriptor_set) = descriptor_set {
+        let bytes = std::fs::read(descriptor_set)
+            .with_context(|| format!("reading {}", descriptor_set.display()))?;
+        return DescriptorPool::decode(bytes.as_slice()).context("decoding descriptor set");
+    }
+    let includes = if includes.is_empty() {
+        protos
+            .iter()
+            .map(|proto| match proto.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+                _ => PathBuf::from("."),
+            })
+            .collect()
+    } else {
+        includes.to_vec()
+    };
+    let pool = protox::Compiler::new(includes)
+        .context("setting up proto compiler")?
+        .open_files(protos)
+        .context("compiling protos")?
+        .descriptor_pool();
+    Ok(pool)
+}
+
+/// A gRPC client for any unary method, described at runtime by a
+/// [`DescriptorPool`].
+#[derive(Clone)]
+pub struct Client {
+    grpc: tonic::client::Grpc<Channel>,
+    path: PathAndQuery,
+    codec: DynamicCodec,
+    request: Request,
+    metadata: Arc<[Metadata]>,
+}
+
+#[derive(Clone)]
+enum Request {
+    Constant(DynamicMessage),
+    Template(Template),
+}
+
+impl Client {
+    /// Connect along `route` and prepare calls to `method`, given as
+    /// `package.Service/Method` or `package.Service.Method`.
+    ///
+    /// `request` is the request message in its JSON mapping. It is rendered
+    /// once up front to catch mistakes before benchmarking.
+    ///
+    /// `metadata` is added to every request.
+    ///
+    /// With `wait_healthy`, wait for the server to report the method's service
+    /// as serving before connecting.
+    pub async fn connect(
+        route: &Route,
+        pool: &DescriptorPool,
+        method: &str,
+        request: Template,
+        metadata: Vec<Metadata>,
+        wait_healthy: Option<Duration>,
+    ) -> anyhow::Result<Self> {
+        let (service, method) = method
+            .rsplit_once('/')
+            .or_else(|| method.rsplit_once('.'))
+            .with_context(|| format!("expected `package.Service/Method`, got `{method}`"))?;
+        let service = pool
+            .get_service_by_name(service)
+            .with_context(|| format!("service `{service}` not found"))?;
+        let method = service
+            .methods()
+            .find(|candidate| candidate.name() == method)
+            .with_context(|| format!("method `{method}` not found in `{}`", service.full_name()))?;
+        if method.is_client_streaming() || method.is_server_streaming() {
+            bail!("only unary methods are supported");
+        }
+
+        let input = method.input();
+        let example = render(&input, &request, &mut rand::rng(), 0)
+            .with_context(|| format!("request does not match `{}`", input.full_name()))?;
+        let request = if request.is_constant() {
+            Request::Constant(example)
+        } else {
+            Request::Template(request)
+        };
+
+        let path = format!("/{}/{}", service.full_name(), method.name())
+            .parse()
+            .context("building method path")?;
+        if let Some(timeout) = wait_healthy {
+            super::grpc::wait_healthy(route, service.full_name(), timeout).await?;
+        }
+        let channel = route.channel().await?;
+        Ok(Self {
+            grpc: tonic::client::Grpc::new(channel),
+            path,
+            codec: DynamicCodec {
+                input,
+                output: method.output(),
+            },
+            request,
+            metadata: metadata.into(),
+        })
+    }
+}
+
+fn render(
+    descriptor: &MessageDescriptor,
+    template: &Template,
+    rng: &mut impl rand::Rng,
+    seq: u64,
+) -> Result<DynamicMessage, serde_json::Error> {
+    let json = template.render(rng, seq);
+    let mut deserializer = serde_json::Deserializer::from_str(&json);
+    let message = DynamicMessage::deserialize(descriptor.clone(), &mut deserializer)?;
+    deserializer.end()?;
+    Ok(message)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("rendering request: {0}")]
+    Render(#[from] serde_json::Error),
+    #[error(transparent)]
+    Status(#[from] Status),
+}
+
+#[async_trait]
+impl super::Target for Client {
+    type Response = DynamicMessage;
+    type Error = Error;
+
+    async fn request(&mut self, seq: u64) -> Result<Received<Self::Response>, Self::Error> {
+        let request = {
+            let mut rng = rand::rng();
+            let message = match &self.request {
+                Request::Constant(message) => message.clone(),
+                Request::Template(template) => render(&self.codec.input, template, &mut rng, seq)?,
+            };
+            let mut request = tonic::Request::new(message);
+            for metadata in self.metadata.iter() {
+                metadata.render_into(request.metadata_mut(), &mut rng, seq)?;
+            }
+            request
+        };
+        self.grpc
+            .ready()
+            .await
+            .map_err(|error| Status::unknown(format!("Service was not ready: {error}")))?;
+        let response = self
+            .grpc
+            .unary(request, self.path.clone(), self.codec.clone())
+            .await?;
+        Ok(super::grpc::received(response))
+    }
+
+    fn failure(error: &Self::Error) -> Failure {
+        match error {
+            Error::Render(_) => Failure::Other,
+            Error::Status(status) => status.into(),
+        }
+    }
+}
+
+/// Encodes and decodes [`DynamicMessage`]s with the protobuf wire format.
+#[derive(Clone)]
+struct DynamicCodec {
+    input: MessageDescriptor,
+    output: MessageDescriptor,
+}
+
+impl Codec for DynamicCodec {
+    type Encode = DynamicMessage;
+    type Decode = DynamicMessage;
+    type Encoder = DynamicEncoder;
+    type Decoder = DynamicDecoder;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        DynamicEncoder
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        DynamicDecoder(self.output.clone())
+    }
+}
+
+struct DynamicEncoder;
+
+impl Encoder for DynamicEncoder {
+    type Item = DynamicMessage;
+    type Error = Status;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut EncodeBuf<'_>) -> Result<(), Self::Error> {
+        item.encode(dst)
+            .map_err(|error| Status::internal(error.to_string()))
+    }
+}
+
+struct DynamicDecoder(MessageDescriptor);
+
+impl Decoder for DynamicDecoder {
+    type Item = DynamicMessage;
+    type Error = Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Self::Item>, Self::Error> {
+        DynamicMessage::decode(self.0.clone(), src)
+            .map(Some)
+            .map_err(|error| Status::internal(error.to_string()))
+    }
+}
@@ -1,35 +1,372 @@
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use std::time::Instant;
+
+use anyhow::Context;
+use anyhow::bail;
 use async_trait::async_trait;
+use bytes::Buf;
+use bytes::Bytes;
+use prost::Message;
+use rand::Rng;
+use tonic::Status;
+use tonic::codec::Codec;
+use tonic::codec::DecodeBuf;
+use tonic::codec::Decoder;
+use tonic::codec::EncodeBuf;
+use tonic::codec::Encoder;
+use tonic::codec::ProstCodec;
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::metadata::AsciiMetadataKey;
+use tonic::metadata::AsciiMetadataValue;
+use tonic::metadata::MetadataMap;
+use tonic::transport::Channel;
+use tonic_health::pb::HealthCheckRequest;
+use tonic_health::pb::health_check_response::ServingStatus;
+use tonic_health::pb::health_client::HealthClient;
+use tracing::info;
+use tracing::warn;
 
+use super::Decoded;
+use super::Failure;
+use super::Received;
+use super::Timings;
+use super::header_bytes;
+use super::route::Route;
+use crate::proto::Empty;
 use crate::proto::Inty;
 use crate::proto::Mixed;
 use crate::proto::Stringy;
-pub use crate::proto::battlebots_service_client::BattlebotsServiceClient as Client;
+use crate::proto::battlebots_service_server::SERVICE_NAME;
+use crate::template::Template;
+use crate::workloads::Invalid;
+use crate::workloads::SEED_HEADER;
+use crate::workloads::inty;
+use crate::workloads::mixed;
+use crate::workloads::stringy;
+
+/// A metadata entry whose value is a [`Template`], given as `key=value`.
+#[derive(Debug, Clone)]
+pub struct Metadata {
+    key: AsciiMetadataKey,
+    value: Template,
+}
+
+impl Metadata {
+    /// Add the entry to `metadata`, rendering its value.
+    #[allow(clippy::result_large_err)]
+    pub fn render_into(
+        &self,
+        metadata: &mut MetadataMap,
+        rng: &mut impl Rng,
+        seq: u64,
+    ) -> Result<(), Status> {
+        let value = AsciiMetadataValue::try_from(self.value.render(rng, seq)).map_err(|_| {
+            Status::invalid_argument(format!("invalid value for metadata `{}`", self.key))
+        })?;
+        metadata.append(self.key.clone(), value);
+        Ok(())
+    }
+}
+
+impl FromStr for Metadata {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, value) = s
+            .split_once('=')
+            .with_context(|| format!("expected `key=value`, got `{s}`"))?;
+        let metadata = Self {
+            key: key.trim().parse().context("parsing metadata key")?,
+            value: value.trim().parse().context("parsing metadata value")?,
+        };
+        // Catch values that can never be sent before benchmarking
+        metadata
+            .render_into(&mut MetadataMap::new(), &mut rand::rng(), 0)
+            .map_err(|status| anyhow::anyhow!("{}", status.message()))?;
+        Ok(metadata)
+    }
+}
+
+/// A client for the built-in workloads, which can decode responses itself
+/// to time the parts of a call.
+#[derive(Clone)]
+pub struct Client {
+    grpc: tonic::client::Grpc<Channel>,
+    paths: Arc<Paths>,
+    metadata: Arc<[Metadata]>,
+    seq: Arc<AtomicU64>,
+    breakdown: bool,
+}
+
+impl Client {
+    /// Create a client adding `metadata` to every request, and timing the
+    /// parts of every call if `breakdown` is set.
+    pub fn new(channel: Channel, metadata: Vec<Metadata>, breakdown: bool) -> Self {
+        Self {
+            grpc: tonic::client::Grpc::new(channel),
+            paths: Arc::new(Paths::new()),
+            metadata: metadata.into(),
+            seq: Arc::new(AtomicU64::new(0)),
+            breakdown,
+        }
+    }
+
+    pub async fn connect(
+        route: &Route,
+        metadata: Vec<Metadata>,
+        breakdown: bool,
+    ) -> Result<Self, tonic::transport::Error> {
+        Ok(Self::new(route.channel().await?, metadata, breakdown))
+    }
+
+    /// A request for a payload, generated from `seed` if there is one.
+    #[allow(clippy::result_large_err)]
+    fn request(&self, seed: Option<u64>) -> Result<tonic::Request<Empty>, Status> {
+        let mut request = tonic::Request::new(Empty {});
+        if let Some(seed) = seed {
+            request.metadata_mut().insert(SEED_HEADER, seed.into());
+        }
+        if !self.metadata.is_empty() {
+            let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+            let mut rng = rand::rng();
+            for metadata in self.metadata.iter() {
+                metadata.render_into(request.metadata_mut(), &mut rng, seq)?;
+            }
+        }
+        Ok(request)
+    }
+
+    /// Make a unary call to the method at `path`, or with `breakdown`, time
+    /// how long it takes until the response headers and the whole message
+    /// arrived, and decoding the message.
+    async fn call<M>(
+        &mut self,
+        path: PathAndQuery,
+        seed: Option<u64>,
+    ) -> Result<Received<M>, Status>
+    where
+        M: Message + Default + Send + Sync + 'static,
+    {
+        let request = self.request(seed)?;
+        self.grpc
+            .ready()
+            .await
+            .map_err(|error| Status::unknown(format!("Service was not ready: {error}")))?;
+        if !self.breakdown {
+            let response = self
+                .grpc
+                .unary(request, path, ProstCodec::<Empty, M>::default())
+                .await?;
+            return Ok(received(response));
+        }
+        let begin = Instant::now();
+        // A unary call looks the same on the wire, but this way the message
+        // is only received once asked for
+        let response = self.grpc.server_streaming(request, path, RawCodec).await?;
+        let headers = begin.elapsed();
+        let (metadata, mut stream, _extensions) = response.into_parts();
+        let message = stream
+            .message()
+            .await?
+            .ok_or_else(|| Status::internal("missing response message"))?;
+        let body = begin.elapsed();
+        let decode_begin = Instant::now();
+        let decoded =
+            M::decode(message.clone()).map_err(|error| Status::internal(error.to_string()))?;
+        let decode = decode_begin.elapsed();
+        stream.trailers().await?;
+        Ok(Received {
+            response: decoded,
+            body_bytes: message.len() as u64,
+            header_bytes: Some(header_bytes(&metadata.into_headers())),
+            timings: Timings {
+                headers: Some(headers),
+                body: Some(body),
+                decode: Some(decode),
+                ..Timings::default()
+            },
+        })
+    }
+}
+
+/// The paths of the methods called by [`Client`], built once rather than for
+/// every call.
+struct Paths {
+    stringy: PathAndQuery,
+    inty: PathAndQuery,
+    mixed: PathAndQuery,
+}
+
+impl Paths {
+    fn new() -> Self {
+        let path = |method| {
+            PathAndQuery::try_from(format!("/{SERVICE_NAME}/{method}"))
+                .expect("method paths are valid")
+        };
+        Self {
+            stringy: path("GetStringy"),
+            inty: path("GetInty"),
+            mixed: path("GetMixed"),
+        }
+    }
+}
+
+/// Poll the server along `route` with the gRPC health checking protocol until
+/// it reports `service` as serving, giving up after `timeout`.
+///
+/// Connection errors are retried, so this can be used while the server is
+/// still starting.
+pub async fn wait_healthy(route: &Route, service: &str, timeout: Duration) -> anyhow::Result<()> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    info!(service, "Waiting for server to become healthy");
+    loop {
+        match check_health(route, service).await {
+            Ok(()) => {
+                info!(service, "Server is healthy");
+                return Ok(());
+            }
+            Err(error) if tokio::time::Instant::now() >= deadline => {
+                return Err(error.context("server did not become healthy in time"));
+            }
+            Err(error) => {
+                warn!(%error, "Server is not healthy yet");
+                tokio::time::sleep(Duration::from_millis(250)).await;
+            }
+        }
+    }
+}
+
+async fn check_health(route: &Route, service: &str) -> anyhow::Result<()> {
+    let channel = route.channel().await.context("connect")?;
+    let response = HealthClient::new(channel)
+        .check(HealthCheckRequest {
+            service: service.to_string(),
+        })
+        .await
+        .context("health check")?;
+    match response.into_inner().status() {
+        ServingStatus::Serving => Ok(()),
+        status => bail!("`{service}` is {}", status.as_str_name()),
+    }
+}
+
+/// The message of `response` along with its size, as encoded without the
+/// gRPC message framing.
+pub fn received<T: prost::Message>(response: tonic::Response<T>) -> Received<T> {
+    let (metadata, message, _extensions) = response.into_parts();
+    Received {
+        body_bytes: message.encoded_len() as u64,
+        header_bytes: Some(header_bytes(&metadata.into_headers())),
+        response: message,
+        timings: Timings::default(),
+    }
+}
 
 #[async_trait]
-impl super::Client for Client<tonic::transport::Channel> {
+impl super::Client for Client {
     type Stringy = Stringy;
     type Inty = Inty;
     type Mixed = Mixed;
-    type Error = tonic::Status;
+    type Error = Status;
+
+    async fn stringy(&mut self, seed: Option<u64>) -> Result<Received<Self::Stringy>, Self::Error> {
+        self.call(self.paths.stringy.clone(), seed).await
+    }
+
+    async fn inty(&mut self, seed: Option<u64>) -> Result<Received<Self::Inty>, Self::Error> {
+        self.call(self.paths.inty.clone(), seed).await
+    }
+
+    async fn mixed(&mut self, seed: Option<u64>) -> Result<Received<Self::Mixed>, Self::Error> {
+        self.call(self.paths.mixed.clone(), seed).await
+    }
+
+    fn failure(status: &Self::Error) -> Failure {
+        status.into()
+    }
+}
+
+/// Encodes [`Empty`] requests and hands out response messages as they were
+/// received, so that they can be decoded separately.
+struct RawCodec;
+
+impl Codec for RawCodec {
+    type Encode = Empty;
+    type Decode = Bytes;
+    type Encoder = RawCodec;
+    type Decoder = RawCodec;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        RawCodec
+    }
 
-    async fn stringy(&mut self) -> Result<Self::Stringy, Self::Error> {
-        let response = self
-            .get_stringy(tonic::Request::new(crate::proto::Empty {}))
-            .await?;
-        Ok(response.into_inner())
+    fn decoder(&mut self) -> Self::Decoder {
+        RawCodec
     }
+}
+
+impl Encoder for RawCodec {
+    type Item = Empty;
+    type Error = Status;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut EncodeBuf<'_>) -> Result<(), Self::Error> {
+        item.encode(dst)
+            .map_err(|error| Status::internal(error.to_string()))
+    }
+}
+
+impl Decoder for RawCodec {
+    type Item = Bytes;
+    type Error = Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Self::Item>, Self::Error> {
+        Ok(Some(src.copy_to_bytes(src.remaining())))
+    }
+}
+
+impl Decoded for Stringy {
+    type Payload = stringy::Payload;
+
+    fn payload(&self) -> Result<Self::Payload, Invalid> {
+        Ok(stringy::Payload {
+            body: self.body.clone().ok_or(Invalid::Missing("body"))?,
+            messages: self.messages.clone(),
+            configuration: self.configuration.clone().into_iter().collect(),
+        })
+    }
+}
+
+impl Decoded for Inty {
+    type Payload = inty::Payload;
 
-    async fn inty(&mut self) -> Result<Self::Inty, Self::Error> {
-        let response = self
-            .get_inty(tonic::Request::new(crate::proto::Empty {}))
-            .await?;
-        Ok(response.into_inner())
+    fn payload(&self) -> Result<Self::Payload, Invalid> {
+        Ok(inty::Payload {
+            header: self.header.clone().ok_or(Invalid::Missing("header"))?,
+            configuration: self.configuration.clone().into_iter().collect(),
+            ids: self.ids.clone(),
+        })
     }
+}
+
+impl Decoded for Mixed {
+    type Payload = mixed::Payload;
 
-    async fn mixed(&mut self) -> Result<Self::Mixed, Self::Error> {
-        let response = self
-            .get_mixed(tonic::Request::new(crate::proto::Empty {}))
-            .await?;
-        Ok(response.into_inner())
+    fn payload(&self) -> Result<Self::Payload, Invalid> {
+        Ok(mixed::Payload {
+            stringy: self
+                .stringy
+                .as_ref()
+                .ok_or(Invalid::Missing("stringy"))?
+                .payload()?,
+            inty: self
+                .inty
+                .as_ref()
+                .ok_or(Invalid::Missing("inty"))?
+                .payload()?,
+        })
     }
 }
@@ -0,0 +1,147 @@
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use rand::Rng;
+use reqwest::Method;
+use reqwest::StatusCode;
+use reqwest::header::HeaderName;
+
+use super::Failure;
+use super::Received;
+use super::Timings;
+use super::header_bytes;
+use super::route::Route;
+use crate::template::Template;
+
+/// A header whose value is a [`Template`], given as `name: value`.
+#[derive(Debug, Clone)]
+pub struct Header {
+    name: HeaderName,
+    value: Template,
+}
+
+impl FromStr for Header {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, value) = s
+            .split_once(':')
+            .with_context(|| format!("expected `name: value`, got `{s}`"))?;
+        Ok(Self {
+            name: name.trim().parse().context("parsing header name")?,
+            value: value.trim().parse().context("parsing header value")?,
+        })
+    }
+}
+
+impl Header {
+    /// The header's name and its value, rendered.
+    pub fn render(&self, rng: &mut impl Rng, seq: u64) -> (HeaderName, String) {
+        (self.name.clone(), self.value.render(rng, seq))
+    }
+}
+
+/// An HTTP client sending the same templated request to any endpoint.
+#[derive(Clone)]
+#[allow(clippy::struct_field_names)]
+pub struct Client {
+    client: reqwest::Client,
+    method: Method,
+    base_url: Arc<str>,
+    path: Template,
+    headers: Arc<[Header]>,
+    body: Option<Template>,
+    expect_status: Arc<[StatusCode]>,
+}
+
+impl Client {
+    /// Create a client sending `method` requests along `route`, to `path`.
+    ///
+    /// If `expect_status` is non-empty, responses with any other status code
+    /// are counted as errors.
+    pub fn new(
+        route: &Route,
+        http1: bool,
+        method: Method,
+        path: Template,
+        headers: Vec<Header>,
+        body: Option<Template>,
+        expect_status: Vec<StatusCode>,
+    ) -> anyhow::Result<Self> {
+        let builder = reqwest::Client::builder().pool_max_idle_per_host(1);
+        let builder = if http1 {
+            builder.http1_only()
+        } else {
+            builder.http2_prior_knowledge()
+        };
+        Ok(Self {
+            client: route.reqwest(builder)?.build()?,
+            method,
+            base_url: route.base_url().into(),
+            path,
+            headers: headers.into(),
+            body,
+            expect_status: expect_status.into(),
+        })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Request(#[from] reqwest::Error),
+    #[error("unexpected status {0}")]
+    Status(StatusCode),
+}
+
+#[async_trait]
+impl super::Target for Client {
+    type Response = bytes::Bytes;
+    type Error = Error;
+
+    async fn request(&mut self, seq: u64) -> Result<Received<Self::Response>, Self::Error> {
+        let request = {
+            let mut rng = rand::rng();
+            let url = format!("{}{}", self.base_url, self.path.render(&mut rng, seq));
+            let mut request = self.client.request(self.method.clone(), url);
+            for header in self.headers.iter() {
+                let (name, value) = header.render(&mut rng, seq);
+                request = request.header(name, value);
+            }
+            if let Some(body) = &self.body {
+                request = request.body(body.render(&mut rng, seq));
+            }
+            request
+        };
+        let begin = Instant::now();
+        let response = request.send().await?;
+        let headers = begin.elapsed();
+        let status = response.status();
+        let header_bytes = header_bytes(response.headers());
+        let body = response.bytes().await?;
+        let body_elapsed = begin.elapsed();
+        if !self.expect_status.is_empty() && !self.expect_status.contains(&status) {
+            return Err(Error::Status(status));
+        }
+        Ok(Received {
+            body_bytes: body.len() as u64,
+            header_bytes: Some(header_bytes),
+            response: body,
+            timings: Timings {
+                headers: Some(headers),
+                body: Some(body_elapsed),
+                ..Timings::default()
+            },
+        })
+    }
+
+    fn failure(error: &Self::Error) -> Failure {
+        match error {
+            Error::Request(error) => error.into(),
+            Error::Status(status) => Failure::Status(*status),
+        }
+    }
+}
@@ -1,7 +1,32 @@
+use std::future::poll_fn;
 use std::sync::Arc;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
 
 use async_trait::async_trait;
+use axum::http::HeaderMap;
+use axum::http::HeaderValue;
+use axum::http::StatusCode;
+use bytes::BufMut;
+use bytes::Bytes;
+use bytes::BytesMut;
+use http_body_util::BodyExt;
+use http_body_util::Empty;
+use hyper_util::rt::TokioExecutor;
+use serde::de::DeserializeOwned;
+use tracing::debug;
 
+use super::Failure;
+use super::Received;
+use super::Timings;
+use super::header_bytes;
+use super::http::Header;
+use super::quic;
+use super::route::Connector;
+use super::route::Route;
+use crate::workloads::Invalid;
+use crate::workloads::SEED_HEADER;
 use crate::workloads::inty;
 use crate::workloads::mixed;
 use crate::workloads::stringy;
@@ -9,58 +34,226 @@ use crate::workloads::stringy;
 #[derive(Clone)]
 #[allow(clippy::struct_field_names)]
 pub struct Client {
-    client: reqwest::Client,
+    transport: Transport,
     url_stringy: Arc<str>,
     url_inty: Arc<str>,
     url_mixed: Arc<str>,
+    headers: Arc<[Header]>,
+    seq: Arc<AtomicU64>,
 }
 
 impl Client {
-    pub fn new(base_url: &str) -> Self {
-        Self {
-            client: reqwest::Client::builder()
+    /// Create a client sending requests along `route`, adding `headers` to
+    /// every one.
+    ///
+    /// Over QUIC, all requests share a single connection, which is opened
+    /// here and not reopened should it fail.
+    pub async fn new(route: &Route, headers: Vec<Header>) -> anyhow::Result<Self> {
+        let transport = if route.is_quic() {
+            let connection = quic::connect(route).await?;
+            let (mut driver, send_request) =
+                h3::client::new(h3_quinn::Connection::new(connection)).await?;
+            tokio::spawn(async move {
+                let error = poll_fn(|cx| driver.poll_close(cx)).await;
+                if !error.is_h3_no_error() {
+                    debug!(%error, "HTTP/3 connection closed");
+                }
+            });
+            Transport::Http3(send_request)
+        } else if route.is_unix() {
+            Transport::Unix(Box::new(
+                hyper_util::client::legacy::Client::builder(TokioExecutor::new())
+                    .http2_only(true)
+                    .pool_max_idle_per_host(1)
+                    .build(route.connector()),
+            ))
+        } else {
+            let builder = reqwest::Client::builder()
                 .http2_prior_knowledge()
-                .pool_max_idle_per_host(1)
-                .build()
-                .unwrap(),
+                .pool_max_idle_per_host(1);
+            Transport::Reqwest(route.reqwest(builder)?.build()?)
+        };
+        let base_url = route.base_url();
+        Ok(Self {
+            transport,
             url_stringy: format!("{base_url}/stringy").into(),
             url_inty: format!("{base_url}/inty").into(),
             url_mixed: format!("{base_url}/mixed").into(),
+            headers: headers.into(),
+            seq: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    async fn get<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        seed: Option<u64>,
+    ) -> Result<Received<T>, Error> {
+        let mut request_headers = HeaderMap::new();
+        if let Some(seed) = seed {
+            request_headers.insert(SEED_HEADER, seed.into());
+        }
+        if !self.headers.is_empty() {
+            let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+            let mut rng = rand::rng();
+            for header in self.headers.iter() {
+                let (name, value) = header.render(&mut rng, seq);
+                request_headers.append(name, HeaderValue::try_from(value)?);
+            }
         }
+        let begin = Instant::now();
+        let (headers, header_bytes, body) = match &self.transport {
+            Transport::Reqwest(client) => {
+                let response = client
+                    .get(url)
+                    .headers(request_headers)
+                    .send()
+                    .await?
+                    .error_for_status()?;
+                let headers = begin.elapsed();
+                let header_bytes = header_bytes(response.headers());
+                (headers, header_bytes, response.bytes().await?)
+            }
+            Transport::Unix(client) => {
+                let mut request = http::Request::get(url).body(Empty::new())?;
+                *request.headers_mut() = request_headers;
+                let response = client.request(request).await?;
+                let headers = begin.elapsed();
+                let status = response.status();
+                if !status.is_success() {
+                    return Err(Error::Status(status));
+                }
+                let header_bytes = header_bytes(response.headers());
+                (
+                    headers,
+                    header_bytes,
+                    response.into_body().collect().await?.to_bytes(),
+                )
+            }
+            Transport::Http3(send_request) => {
+                let mut request = http::Request::get(url).body(())?;
+                *request.headers_mut() = request_headers;
+                let mut stream = send_request.clone().send_request(request).await?;
+                stream.finish().await?;
+                let response = stream.recv_response().await?;
+                let headers = begin.elapsed();
+                let status = response.status();
+                if !status.is_success() {
+                    return Err(Error::Status(status));
+                }
+                let header_bytes = header_bytes(response.headers());
+                let mut body = BytesMut::new();
+                while let Some(chunk) = stream.recv_data().await? {
+                    body.put(chunk);
+                }
+                (headers, header_bytes, body.freeze())
+            }
+        };
+        let body_elapsed = begin.elapsed();
+        let decode_begin = Instant::now();
+        let decoded = serde_json::from_slice(&body)?;
+        let decode = decode_begin.elapsed();
+        Ok(Received {
+            response: decoded,
+            body_bytes: body.len() as u64,
+            header_bytes: Some(header_bytes),
+            timings: Timings {
+                headers: Some(headers),
+                body: Some(body_elapsed),
+                decode: Some(decode),
+                ..Timings::default()
+            },
+        })
     }
 }
 
+/// How requests are sent, as reqwest can neither connect over Unix domain
+/// sockets nor speak HTTP/3.
+#[derive(Clone)]
+enum Transport {
+    Reqwest(reqwest::Client),
+    Unix(Box<hyper_util::client::legacy::Client<Connector, Empty<Bytes>>>),
+    Http3(h3::client::SendRequest<h3_quinn::OpenStreams, Bytes>),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("invalid header value: {0}")]
+    Header(#[from] axum::http::header::InvalidHeaderValue),
+    #[error("building request: {0}")]
+    Build(#[from] http::Error),
+    #[error(transparent)]
+    Request(#[from] reqwest::Error),
+    #[error(transparent)]
+    Unix(#[from] hyper_util::client::legacy::Error),
+    #[error("receiving response body: {0}")]
+    Body(#[from] hyper::Error),
+    #[error(transparent)]
+    Http3(#[from] h3::error::StreamError),
+    #[error("unexpected status {0}")]
+    Status(StatusCode),
+    #[error("decoding response body: {0}")]
+    Decode(#[from] serde_json::Error),
+}
+
 #[async_trait]
 impl super::Client for Client {
     type Stringy = stringy::Payload;
     type Inty = inty::Payload;
     type Mixed = mixed::Payload;
-    type Error = reqwest::Error;
-
-    async fn stringy(&mut self) -> Result<Self::Stringy, Self::Error> {
-        self.client
-            .get(self.url_stringy.as_ref())
-            .send()
-            .await?
-            .json()
-            .await
+    type Error = Error;
+
+    async fn stringy(&mut self, seed: Option<u64>) -> Result<Received<Self::Stringy>, Self::Error> {
+        self.get(&self.url_stringy, seed).await
+    }
+
+    async fn inty(&mut self, seed: Option<u64>) -> Result<Received<Self::Inty>, Self::Error> {
+        self.get(&self.url_inty, seed).await
+    }
+
+    async fn mixed(&mut self, seed: Option<u64>) -> Result<Received<Self::Mixed>, Self::Error> {
+        self.get(&self.url_mixed, seed).await
+    }
+
+    fn failure(error: &Self::Error) -> Failure {
+        match error {
+            Error::Request(error) => error.into(),
+            Error::Unix(error) if error.is_connect() => Failure::Connection,
+            Error::Http3(
+                h3::error::StreamError::ConnectionError { .. }
+                | h3::error::StreamError::RemoteClosing { .. },
+            ) => Failure::Connection,
+            Error::Status(status) => Failure::Status(*status),
+            Error::Header(_)
+            | Error::Build(_)
+            | Error::Unix(_)
+            | Error::Body(_)
+            | Error::Http3(_)
+            | Error::Decode(_) => Failure::Other,
+        }
+    }
+}
+
+impl super::Decoded for stringy::Payload {
+    type Payload = Self;
+
+    fn payload(&self) -> Result<Self::Payload, Invalid> {
+        Ok(self.clone())
     }
+}
 
-    async fn inty(&mut self) -> Result<Self::Inty, Self::Error> {
-        self.client
-            .get(self.url_inty.as_ref())
-            .send()
-            .await?
-            .json()
-            .await
+impl super::Decoded for inty::Payload {
+    type Payload = Self;
+
+    fn payload(&self) -> Result<Self::Payload, Invalid> {
+        Ok(self.clone())
     }
+}
+
+impl super::Decoded for mixed::Payload {
+    type Payload = Self;
 
-    async fn mixed(&mut self) -> Result<Self::Mixed, Self::Error> {
-        self.client
-            .get(self.url_mixed.as_ref())
-            .send()
-            .await?
-            .json()
-            .await
+    fn payload(&self) -> Result<Self::Payload, Invalid> {
+        Ok(self.clone())
     }
 }
@@ -0,0 +1,320 @@
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::future::Future;
+use std::io;
+use std::net::IpAddr;
+use std::net::SocketAddr;
+use std::net::ToSocketAddrs;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::task::Context;
+use std::task::Poll;
+
+use anyhow::ensure;
+use http::Uri;
+use http::uri::Authority;
+use hyper_util::client::legacy::connect::Connected;
+use hyper_util::client::legacy::connect::Connection;
+use hyper_util::rt::TokioIo;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWrite;
+use tokio::io::AsyncWriteExt;
+use tokio::io::ReadBuf;
+use tokio::net::TcpStream;
+use tokio::net::UnixStream;
+use tokio_util::either::Either;
+use tonic::transport::Channel;
+use tonic::transport::Endpoint;
+use tower_service::Service;
+
+/// How to reach the server, apart from which authority requests are
+/// addressed to.
+#[derive(Debug, Clone)]
+pub struct Route {
+    authority: Authority,
+    /// Where to open connections instead of the authority
+    connect_to: Option<Authority>,
+    /// HTTP proxy to send requests through
+    proxy: Option<Authority>,
+    /// Unix domain socket to connect to instead of TCP
+    uds: Option<PathBuf>,
+    /// Whether requests go over HTTP/3 on QUIC instead of TCP
+    quic: bool,
+}
+
+impl Route {
+    /// A route to `authority`, optionally connecting to `connect_to` instead
+    /// and through the HTTP `proxy`, given as `http://host:port`, or to the
+    /// Unix domain socket `uds`.
+    pub fn new(
+        authority: Authority,
+        connect_to: Option<Authority>,
+        proxy: Option<Uri>,
+        uds: Option<PathBuf>,
+    ) -> anyhow::Result<Self> {
+        ensure!(
+            uds.is_none() || (connect_to.is_none() && proxy.is_none()),
+            "--uds can't be combined with --connect-to or --proxy"
+        );
+        if let Some(connect_to) = &connect_to {
+            ensure!(
+                connect_to.port_u16().is_some(),
+                "--connect-to must be given as `host:port`, got `{connect_to}`"
+            );
+        }
+        let proxy = proxy
+            .map(|proxy| {
+                ensure!(
+                    proxy.scheme_str() == Some("http"),
+                    "--proxy must be an `http://` URL, got `{proxy}`"
+                );
+                Ok(proxy.into_parts().authority.expect("http URLs have one"))
+            })
+            .transpose()?;
+        Ok(Self {
+            authority,
+            connect_to,
+            proxy,
+            uds,
+            quic: false,
+        })
+    }
+
+    /// The same route for HTTP/3 over QUIC, which can't go through a proxy
+    /// or over a Unix domain socket.
+    pub fn over_quic(self) -> anyhow::Result<Self> {
+        ensure!(
+            self.uds.is_none() && self.proxy.is_none(),
+            "HTTP/3 can't be combined with --uds or --proxy"
+        );
+        Ok(Self { quic: true, ..self })
+    }
+
+    /// The authority requests are addressed to.
+    pub fn authority(&self) -> &Authority {
+        &self.authority
+    }
+
+    /// The base URL of requests, without a trailing slash.
+    pub fn base_url(&self) -> String {
+        let scheme = if self.quic { "https" } else { "http" };
+        format!("{scheme}://{}", self.authority)
+    }
+
+    /// Apply the route to a [`reqwest::Client`] about to be built.
+    ///
+    /// `--connect-to` is applied by resolving the host of the authority to
+    /// it, so the authority needs a host name rather than an IP address, and
+    /// no port other than that of `--connect-to`.
+    pub fn reqwest(
+        &self,
+        builder: reqwest::ClientBuilder,
+    ) -> anyhow::Result<reqwest::ClientBuilder> {
+        ensure!(
+            self.uds.is_none(),
+            "this client can't connect over Unix domain sockets"
+        );
+        let mut builder = match &self.proxy {
+            Some(proxy) => builder.proxy(reqwest::Proxy::all(format!("http://{proxy}"))?),
+            None => builder.no_proxy(),
+        };
+        if let Some(connect_to) = &self.connect_to {
+            let host = self.authority.host();
+            ensure!(
+                host.parse::<IpAddr>().is_err() && !host.starts_with('['),
+                "--connect-to needs a host name rather than an IP address in the authority for \
+                 HTTP clients, got `{host}`"
+            );
+            ensure!(
+                self.authority.port_u16().is_none()
+                    || self.authority.port_u16() == connect_to.port_u16(),
+                "--connect-to needs the same port as the authority or none in it for HTTP \
+                 clients, got `{}`",
+                self.authority
+            );
+            let addrs: Vec<_> = socket_addr(connect_to, 80).to_socket_addrs()?.collect();
+            builder = builder.resolve_to_addrs(host, &addrs);
+        }
+        Ok(builder)
+    }
+
+    /// Whether connections go over a Unix domain socket.
+    pub fn is_unix(&self) -> bool {
+        self.uds.is_some()
+    }
+
+    /// Whether requests go over HTTP/3 on QUIC.
+    pub fn is_quic(&self) -> bool {
+        self.quic
+    }
+
+    /// The transport of connections, to tell runs apart by.
+    pub fn transport(&self) -> &'static str {
+        if self.is_unix() {
+            "unix"
+        } else if self.quic {
+            "quic"
+        } else {
+            "tcp"
+        }
+    }
+
+    /// The host name to present to the server in QUIC handshakes.
+    pub fn server_name(&self) -> &str {
+        socket_addr(&self.authority, 443).0
+    }
+
+    /// Resolve where to send QUIC packets, defaulting to port 443 as in
+    /// `https://` URLs.
+    pub async fn quic_addr(&self) -> io::Result<SocketAddr> {
+        let target = self.connect_to.as_ref().unwrap_or(&self.authority);
+        tokio::net::lookup_host(socket_addr(target, 443))
+            .await?
+            .next()
+            .ok_or_else(|| io::Error::other(format!("{target} resolves to no address")))
+    }
+
+    /// Open a connection along the route, tunneling through the proxy with
+    /// `CONNECT` if there is one.
+    pub async fn connect(&self) -> io::Result<Stream> {
+        if let Some(uds) = &self.uds {
+            return Ok(Stream(Either::Right(UnixStream::connect(uds).await?)));
+        }
+        let target = self.connect_to.as_ref().unwrap_or(&self.authority);
+        let stream = match &self.proxy {
+            Some(proxy) => {
+                let stream = TcpStream::connect(socket_addr(proxy, 80)).await?;
+                tunnel(stream, target).await?
+            }
+            None => TcpStream::connect(socket_addr(target, 80)).await?,
+        };
+        stream.set_nodelay(true)?;
+        Ok(Stream(Either::Left(stream)))
+    }
+
+    /// Connect a gRPC [`Channel`] along the route.
+    pub async fn channel(&self) -> Result<Channel, tonic::transport::Error> {
+        let endpoint = Endpoint::from_shared(self.base_url())?;
+        if self.connect_to.is_none() && self.proxy.is_none() && self.uds.is_none() {
+            endpoint.connect().await
+        } else {
+            endpoint.connect_with_connector(self.connector()).await
+        }
+    }
+
+    /// A connector opening connections along the route, for hyper and
+    /// tonic.
+    pub fn connector(&self) -> Connector {
+        Connector(self.clone())
+    }
+}
+
+impl Display for Route {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.authority)?;
+        if let Some(uds) = &self.uds {
+            return write!(f, ", over {}", uds.display());
+        }
+        if self.quic {
+            write!(f, ", over QUIC")?;
+        }
+        if let Some(connect_to) = &self.connect_to {
+            write!(f, ", connecting to {connect_to}")?;
+        }
+        match &self.proxy {
+            Some(proxy) => write!(f, ", through proxy {proxy}"),
+            None if self.connect_to.is_none() => write!(f, ", direct"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The host and port of `authority`, defaulting to `default_port`.
+fn socket_addr(authority: &Authority, default_port: u16) -> (&str, u16) {
+    let host = authority.host();
+    let host = host
+        .strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host);
+    (host, authority.port_u16().unwrap_or(default_port))
+}
+
+/// Ask the HTTP proxy at the other end of `stream` to open a tunnel to
+/// `target`.
+async fn tunnel(mut stream: TcpStream, target: &Authority) -> io::Result<TcpStream> {
+    let target = format!("{}:{}", target.host(), socket_addr(target, 80).1);
+    stream
+        .write_all(format!("CONNECT {target} HTTP/1.1\r\nHost: {target}\r\n\r\n").as_bytes())
+        .await?;
+    // Read byte by byte, as the server may speak first once the tunnel is
+    // open and nothing past the response may be consumed
+    let mut response = Vec::new();
+    while !response.ends_with(b"\r\n\r\n") {
+        response.push(stream.read_u8().await?);
+    }
+    let response = String::from_utf8_lossy(&response);
+    let status = response.lines().next().unwrap_or_default();
+    if status.split_whitespace().nth(1) != Some("200") {
+        return Err(io::Error::other(format!(
+            "proxy refused to connect to {target}: {status}"
+        )));
+    }
+    Ok(stream)
+}
+
+/// A connection along a [`Route`].
+pub struct Stream(Either<TcpStream, UnixStream>);
+
+impl AsyncRead for Stream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for Stream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+}
+
+impl Connection for Stream {
+    fn connected(&self) -> Connected {
+        Connected::new()
+    }
+}
+
+/// Opens connections along a [`Route`].
+#[derive(Clone)]
+pub struct Connector(Route);
+
+impl Service<Uri> for Connector {
+    type Response = TokioIo<Stream>;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = io::Result<Self::Response>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _uri: Uri) -> Self::Future {
+        let route = self.0.clone();
+        Box::pin(async move { route.connect().await.map(TokioIo::new) })
+    }
+}
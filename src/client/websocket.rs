@@ -0,0 +1,245 @@
+use std::io;
+use std::sync::Arc;
+use std::time::Instant;
+
+use async_trait::async_trait;
+use clap::ValueEnum;
+use futures::SinkExt;
+use futures::StreamExt;
+use serde::de::DeserializeOwned;
+use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite;
+use tokio_tungstenite::tungstenite::Message;
+
+use super::Decoded;
+use super::Failure;
+use super::Received;
+use super::Timings;
+use super::route::Route;
+use super::route::Stream;
+use crate::FrameFormat;
+use crate::Workload;
+use crate::proto;
+use crate::workloads::Invalid;
+use crate::workloads::Request;
+use crate::workloads::inty;
+use crate::workloads::mixed;
+use crate::workloads::stringy;
+
+/// A client sending requests over a WebSocket, one frame each way.
+///
+/// Every clone has a socket of its own, opened with its first request and
+/// reopened with the next one after any error.
+pub struct Client {
+    route: Arc<Route>,
+    format: FrameFormat,
+    socket: Option<WebSocketStream<Stream>>,
+}
+
+impl Client {
+    pub fn new(route: Route, format: FrameFormat) -> Self {
+        Self {
+            route: Arc::new(route),
+            format,
+            socket: None,
+        }
+    }
+
+    async fn connect(&self) -> Result<WebSocketStream<Stream>, Error> {
+        let format = self.format.to_possible_value().expect("no skipped formats");
+        let url = format!(
+            "ws://{}/ws?format={}",
+            self.route.authority(),
+            format.get_name()
+        );
+        let stream = self.route.connect().await?;
+        let (socket, _response) = tokio_tungstenite::client_async(url, stream).await?;
+        Ok(socket)
+    }
+
+    /// Request a payload of `workload`, opening the socket first if need be.
+    async fn request<T, P>(
+        &mut self,
+        workload: Workload,
+        seed: Option<u64>,
+    ) -> Result<Received<Frame<T, P>>, Error>
+    where
+        T: DeserializeOwned,
+        P: prost::Message + Default,
+    {
+        let connect = if self.socket.is_some() {
+            None
+        } else {
+            let begin = Instant::now();
+            self.socket = Some(self.connect().await?);
+            Some(begin.elapsed())
+        };
+        let socket = self.socket.as_mut().expect("socket was just opened");
+        let received = exchange(socket, self.format, Request { workload, seed }).await;
+        if received.is_err() {
+            self.socket = None;
+        }
+        let received = received?;
+        Ok(Received {
+            timings: Timings {
+                connect,
+                ..received.timings
+            },
+            ..received
+        })
+    }
+}
+
+impl Clone for Client {
+    fn clone(&self) -> Self {
+        Self {
+            route: self.route.clone(),
+            format: self.format,
+            socket: None,
+        }
+    }
+}
+
+/// Send `request` and wait for the frame answering it.
+async fn exchange<T, P>(
+    socket: &mut WebSocketStream<Stream>,
+    format: FrameFormat,
+    request: Request,
+) -> Result<Received<Frame<T, P>>, Error>
+where
+    T: DeserializeOwned,
+    P: prost::Message + Default,
+{
+    let message = match format {
+        FrameFormat::Json => {
+            Message::Text(serde_json::to_string(&request).expect("requests serialize to JSON"))
+        }
+        FrameFormat::Protobuf => {
+            let workload = match request.workload {
+                Workload::Stringy => proto::Workload::Stringy,
+                Workload::Inty => proto::Workload::Inty,
+                Workload::Mixed => proto::Workload::Mixed,
+            };
+            let request = proto::WorkloadRequest {
+                workload: workload.into(),
+                seed: request.seed,
+            };
+            Message::Binary(prost::Message::encode_to_vec(&request))
+        }
+    };
+    let begin = Instant::now();
+    socket.send(message).await?;
+    let body =
+        loop {
+            match socket.next().await.ok_or_else(|| {
+                Error::Closed("connection ended without a close frame".to_owned())
+            })?? {
+                Message::Text(text) if format == FrameFormat::Json => break text.into_bytes(),
+                Message::Binary(bytes) if format == FrameFormat::Protobuf => break bytes,
+                Message::Ping(_) | Message::Pong(_) | Message::Frame(_) => {}
+                Message::Close(frame) => {
+                    let reason = frame.map_or_else(
+                        || "no reason given".to_owned(),
+                        |frame| frame.reason.into_owned(),
+                    );
+                    return Err(Error::Closed(reason));
+                }
+                Message::Text(_) => return Err(Error::Frame("text")),
+                Message::Binary(_) => return Err(Error::Frame("binary")),
+            }
+        };
+    let body_elapsed = begin.elapsed();
+    let decode_begin = Instant::now();
+    let decoded = match format {
+        FrameFormat::Json => Frame::Json(serde_json::from_slice(&body)?),
+        FrameFormat::Protobuf => Frame::Protobuf(P::decode(&*body)?),
+    };
+    let decode = decode_begin.elapsed();
+    Ok(Received {
+        response: decoded,
+        body_bytes: body.len() as u64,
+        header_bytes: None,
+        timings: Timings {
+            body: Some(body_elapsed),
+            decode: Some(decode),
+            ..Timings::default()
+        },
+    })
+}
+
+/// A payload decoded from a frame of either format.
+pub enum Frame<T, P> {
+    Json(T),
+    Protobuf(P),
+}
+
+impl<T, P> Decoded for Frame<T, P>
+where
+    T: Decoded,
+    P: Decoded<Payload = T::Payload>,
+{
+    type Payload = T::Payload;
+
+    fn payload(&self) -> Result<Self::Payload, Invalid> {
+        match self {
+            Self::Json(payload) => payload.payload(),
+            Self::Protobuf(message) => message.payload(),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("connecting: {0}")]
+    Connect(#[from] io::Error),
+    #[error(transparent)]
+    Websocket(#[from] Box<tungstenite::Error>),
+    #[error("server closed the socket: {0}")]
+    Closed(String),
+    #[error("unexpected {0} frame")]
+    Frame(&'static str),
+    #[error("decoding JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("decoding protobuf: {0}")]
+    Protobuf(#[from] prost::DecodeError),
+}
+
+impl From<tungstenite::Error> for Error {
+    fn from(error: tungstenite::Error) -> Self {
+        Self::Websocket(Box::new(error))
+    }
+}
+
+#[async_trait]
+impl super::Client for Client {
+    type Stringy = Frame<stringy::Payload, proto::Stringy>;
+    type Inty = Frame<inty::Payload, proto::Inty>;
+    type Mixed = Frame<mixed::Payload, proto::Mixed>;
+    type Error = Error;
+
+    async fn stringy(&mut self, seed: Option<u64>) -> Result<Received<Self::Stringy>, Self::Error> {
+        self.request(Workload::Stringy, seed).await
+    }
+
+    async fn inty(&mut self, seed: Option<u64>) -> Result<Received<Self::Inty>, Self::Error> {
+        self.request(Workload::Inty, seed).await
+    }
+
+    async fn mixed(&mut self, seed: Option<u64>) -> Result<Received<Self::Mixed>, Self::Error> {
+        self.request(Workload::Mixed, seed).await
+    }
+
+    fn failure(error: &Self::Error) -> Failure {
+        match error {
+            Error::Websocket(error) => match &**error {
+                tungstenite::Error::Http(response) => Failure::Status(response.status()),
+                tungstenite::Error::ConnectionClosed
+                | tungstenite::Error::AlreadyClosed
+                | tungstenite::Error::Io(_) => Failure::Connection,
+                _ => Failure::Other,
+            },
+            Error::Connect(_) | Error::Closed(_) => Failure::Connection,
+            Error::Frame(_) | Error::Json(_) | Error::Protobuf(_) => Failure::Other,
+        }
+    }
+}
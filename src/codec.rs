@@ -0,0 +1,219 @@
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::hint::black_box;
+use std::time::Duration;
+use std::time::Instant;
+
+use clap::ValueEnum;
+use hdrhistogram::Histogram;
+use prost::Message;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use tracing::info;
+use tracing::instrument;
+
+use crate::Codec;
+use crate::Encoding;
+use crate::Workload;
+use crate::bench::Latencies;
+use crate::proto;
+use crate::workloads::Rand;
+use crate::workloads::inty;
+use crate::workloads::mixed;
+use crate::workloads::stringy;
+
+/// Measure how long it takes to encode and decode payloads, without any
+/// networking involved.
+#[instrument(skip_all)]
+pub fn benchmark(codec: Codec) -> anyhow::Result<Report> {
+    let Codec {
+        workload,
+        encoding,
+        payloads,
+        duration,
+    } = codec;
+    let encodings = if encoding.is_empty() {
+        Encoding::value_variants().to_vec()
+    } else {
+        encoding
+    };
+    let duration = Duration::from_secs(duration);
+
+    info!("Generating {payloads} payloads");
+    let mut rng = rand::rng();
+    let payloads = 0..payloads.get();
+    let encodings = match workload {
+        Workload::Inty => benchmark_payloads::<_, proto::Inty>(
+            &payloads
+                .map(|_| inty::Payload::rand(&mut rng))
+                .collect::<Vec<_>>(),
+            &encodings,
+            duration,
+        ),
+        Workload::Stringy => benchmark_payloads::<_, proto::Stringy>(
+            &payloads
+                .map(|_| stringy::Payload::rand(&mut rng))
+                .collect::<Vec<_>>(),
+            &encodings,
+            duration,
+        ),
+        Workload::Mixed => benchmark_payloads::<_, proto::Mixed>(
+            &payloads
+                .map(|_| mixed::Payload::rand(&mut rng))
+                .collect::<Vec<_>>(),
+            &encodings,
+            duration,
+        ),
+    }?;
+    Ok(Report { encodings })
+}
+
+fn benchmark_payloads<T, P>(
+    payloads: &[T],
+    encodings: &[Encoding],
+    duration: Duration,
+) -> anyhow::Result<Vec<EncodingReport>>
+where
+    T: Serialize + DeserializeOwned + Clone,
+    P: Message + Default + From<T>,
+{
+    let mut reports = Vec::with_capacity(encodings.len());
+    for &encoding in encodings {
+        info!(
+            ?encoding,
+            "Benchmarking for {:.1} s",
+            duration.as_secs_f64()
+        );
+        let report = match encoding {
+            Encoding::SerdeJson => measure(
+                encoding,
+                payloads,
+                duration,
+                |payload| Ok(serde_json::to_vec(payload)?),
+                |bytes| Ok(serde_json::from_slice::<T>(bytes)?),
+            ),
+            Encoding::SimdJson => measure(
+                encoding,
+                payloads,
+                duration,
+                |payload| Ok(simd_json::to_vec(payload)?),
+                |bytes| Ok(simd_json::from_slice::<T>(bytes)?),
+            ),
+            Encoding::Prost => measure(
+                encoding,
+                &payloads.iter().cloned().map(P::from).collect::<Vec<_>>(),
+                duration,
+                |message| Ok(message.encode_to_vec()),
+                |bytes| Ok(P::decode(&*bytes)?),
+            ),
+        }?;
+        reports.push(report);
+    }
+    Ok(reports)
+}
+
+/// Encode and decode `messages` round-robin for `duration`, timing each
+/// operation in nanoseconds.
+fn measure<M, D>(
+    encoding: Encoding,
+    messages: &[M],
+    duration: Duration,
+    encode: impl Fn(&M) -> anyhow::Result<Vec<u8>>,
+    decode: impl Fn(&mut [u8]) -> anyhow::Result<D>,
+) -> anyhow::Result<EncodingReport> {
+    let mut encode_nanoseconds = Histogram::new(3)?;
+    let mut decode_nanoseconds = Histogram::new(3)?;
+    let mut bytes_per_payload = Histogram::new(3)?;
+    let begin = Instant::now();
+    for message in messages.iter().cycle() {
+        if begin.elapsed() >= duration {
+            break;
+        }
+        let encode_begin = Instant::now();
+        let mut bytes = black_box(encode(black_box(message))?);
+        let encode_elapsed = encode_begin.elapsed();
+        let decode_begin = Instant::now();
+        black_box(decode(black_box(&mut bytes))?);
+        let decode_elapsed = decode_begin.elapsed();
+        encode_nanoseconds.record(u64::try_from(encode_elapsed.as_nanos())?)?;
+        decode_nanoseconds.record(u64::try_from(decode_elapsed.as_nanos())?)?;
+        bytes_per_payload.record(u64::try_from(bytes.len())?)?;
+    }
+    Ok(EncodingReport {
+        encoding,
+        encode_nanoseconds,
+        decode_nanoseconds,
+        bytes_per_payload,
+    })
+}
+
+struct EncodingReport {
+    encoding: Encoding,
+    encode_nanoseconds: Histogram<u64>,
+    decode_nanoseconds: Histogram<u64>,
+    bytes_per_payload: Histogram<u64>,
+}
+
+pub struct Report {
+    encodings: Vec<EncodingReport>,
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for report in &self.encodings {
+            write!(f, "{report}")?;
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for EncodingReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = self
+            .encoding
+            .to_possible_value()
+            .expect("no skipped encodings");
+        writeln!(f, "{}:", name.get_name())?;
+        writeln!(f, "Encode latencies:")?;
+        write!(
+            f,
+            "{}",
+            Latencies {
+                histogram: &self.encode_nanoseconds,
+                unit: "ns",
+            }
+        )?;
+        writeln!(f, "Decode latencies:")?;
+        write!(
+            f,
+            "{}",
+            Latencies {
+                histogram: &self.decode_nanoseconds,
+                unit: "ns",
+            }
+        )?;
+        writeln!(f)?;
+        let mean_bytes = self.bytes_per_payload.mean();
+        writeln!(f, "         Operations: {}", self.encode_nanoseconds.len())?;
+        writeln!(
+            f,
+            "  Bytes per payload: {mean_bytes:.1} mean, {} p99",
+            self.bytes_per_payload.value_at_quantile(0.99)
+        )?;
+        for (operation, histogram) in [
+            ("Encode", &self.encode_nanoseconds),
+            ("Decode", &self.decode_nanoseconds),
+        ] {
+            let nanoseconds_per_operation = histogram.mean();
+            let operations_per_second = 1e9 / nanoseconds_per_operation;
+            writeln!(
+                f,
+                "{operation:>19}: {nanoseconds_per_operation:.1} ns/op, \
+                 {operations_per_second:.0} ops/s, {:.2} MB/s",
+                mean_bytes * operations_per_second / 1e6,
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -1,21 +1,38 @@
 use std::net::SocketAddr;
 use std::num::NonZeroU32;
+use std::num::NonZeroU64;
 use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Context;
+use anyhow::ensure;
 use clap::Args;
 use clap::Parser;
 use clap::Subcommand;
 use clap::ValueEnum;
-use futures::future::try_join;
+use futures::future::BoxFuture;
+use futures::future::try_join_all;
+use http::Uri;
+use http::uri::Authority;
+use serde::Deserialize;
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
 use tracing::info;
+use tracing::warn;
 
 use crate::bench::benchmark;
+use crate::client::Builtin;
+use crate::client::route::Route;
+use crate::template::Template;
 
 mod bench;
 mod client;
+mod codec;
 mod proto;
 mod server;
+mod template;
 mod workloads;
 
 #[derive(Debug, Parser)]
@@ -25,11 +42,14 @@ struct Cli {
 }
 
 #[derive(Debug, Subcommand)]
+#[allow(clippy::large_enum_variant)]
 enum Program {
     /// Run as server, serving both gRPC and HTTP/REST requests
     Server(Server),
     /// Run as either a gRPC client or an HTTP/REST client
     Client(Client),
+    /// Measure encoding and decoding of payloads, without networking
+    Codec(Codec),
 }
 
 #[derive(Debug, Args)]
@@ -40,34 +60,246 @@ struct Server {
     /// The interface on which to bind the gRPC server's listener
     #[arg(long, default_value = "0.0.0.0:55556")]
     addr_grpc: SocketAddr,
+    /// Serve both HTTP/REST and gRPC on a single listener bound to this
+    /// interface instead, routing by `content-type`
+    #[arg(long, conflicts_with_all = ["addr_http", "addr_grpc"])]
+    addr: Option<SocketAddr>,
+    /// Also serve HTTP/REST over HTTP/3 on this UDP interface, with a
+    /// self-signed certificate generated on start
+    #[arg(long)]
+    addr_h3: Option<SocketAddr>,
+    /// Also serve HTTP/REST on a Unix domain socket at this path
+    #[arg(long)]
+    uds_http: Option<PathBuf>,
+    /// Also serve gRPC on a Unix domain socket at this path
+    #[arg(long)]
+    uds_grpc: Option<PathBuf>,
+    /// Seconds to wait for in-flight requests to finish after receiving
+    /// SIGINT or SIGTERM
+    #[arg(long, default_value = "10")]
+    drain_timeout: u64,
+    /// Where response payloads come from
+    #[arg(long, value_enum, default_value = "random")]
+    payload_mode: PayloadMode,
+    /// How many payloads per workload to pre-generate with
+    /// `--payload-mode pool`
+    #[arg(long, default_value = "1000")]
+    pool_size: NonZeroUsize,
+    /// Generate payloads deterministically from this seed
+    ///
+    /// The `n`th response of each workload is generated from `seed + n`,
+    /// so HTTP/REST and gRPC serve the same sequence. In random mode, a
+    /// request can also ask for a specific seed with the
+    /// `x-battlebots-seed` header or metadata.
+    ///
+    /// JSON responses are then the same bytes in every run, while protobuf
+    /// map entries may come in any order.
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+#[derive(Debug, Clone, ValueEnum, Eq, PartialEq, Copy)]
+enum PayloadMode {
+    /// Generate a new payload for every request
+    Random,
+    /// Pre-generate payloads at startup and serve them round-robin
+    Pool,
+    /// Serve one payload, serialized once at startup
+    Fixed,
 }
 
 #[derive(Debug, Args)]
 struct Bench {
     /// The type of workload
+    ///
+    /// Required by the built-in gRPC and HTTP/REST clients
     #[arg(long, value_enum)]
-    workload: Workload,
+    workload: Option<Workload>,
+    /// Check responses of the built-in clients against the invariants of
+    /// the workload, counting violations as invalid responses
+    ///
+    /// Only for the `grpc`, `rest` and `websocket` clients
+    #[arg(long)]
+    validate: bool,
+    /// Ask the server for the payload generated from `seed + n` for the
+    /// `n`th request of the built-in clients
+    ///
+    /// Sent as the `x-battlebots-seed` header or metadata, which the server
+    /// honors with `--payload-mode random` and refuses otherwise. With
+    /// `--validate`, responses must then equal the expected payload
+    /// exactly. Only for the `grpc`, `rest` and `websocket` clients.
+    #[arg(long)]
+    seed: Option<u64>,
     /// How many workers to use (instances of clients, (maybe) number of
     /// connections)
     #[arg(long)]
     workers: NonZeroUsize,
-    /// Upper limit of requests per second
+    /// How workers pace their requests
+    #[arg(long, value_enum, default_value = "open")]
+    mode: Mode,
+    /// Upper limit of requests per second, shared by all workers in open
+    /// mode and for each worker in per-worker mode
+    ///
+    /// Required unless in closed mode, which it doesn't apply to
     #[arg(long)]
-    rate: NonZeroU32,
+    rate: Option<NonZeroU32>,
+    /// When requests are sent in open and per-worker mode
+    #[arg(long, value_enum, default_value = "uniform", requires = "rate")]
+    arrival: Arrival,
+    /// Milliseconds of each burst with `--arrival bursty`
+    #[arg(long, default_value = "100", requires = "rate")]
+    burst_on: NonZeroU64,
+    /// Milliseconds between bursts with `--arrival bursty`
+    #[arg(long, default_value = "900", requires = "rate")]
+    burst_off: u64,
+    /// Mean milliseconds each worker waits between receiving a response and
+    /// sending its next request in closed mode
+    #[arg(long, default_value = "0", conflicts_with = "rate")]
+    think_time: u64,
+    /// How think times are distributed around their mean
+    #[arg(long, value_enum, default_value = "constant", conflicts_with = "rate")]
+    think_time_distribution: Distribution,
     /// How many seconds to run the benchmark
+    #[arg(long, required_unless_present = "requests")]
+    duration: Option<u64>,
+    /// How many requests to measure, across all workers
+    ///
+    /// With `--duration`, the benchmark stops at whichever comes first
     #[arg(long)]
-    duration: u64,
+    requests: Option<NonZeroU64>,
     /// Microseconds of jitter for rate limiter
-    #[arg(long, default_value = "20")]
+    #[arg(long, default_value = "20", requires = "rate")]
     jitter: u64,
     /// Continue benchmarking if error during service call
     ///
     /// Default behavior is to stop at first error
     #[arg(long)]
     continue_on_error: bool,
+    /// Send every request at most this many times, retrying failures that
+    /// may succeed when repeated
+    ///
+    /// Connection failures are retried, as are the statuses and codes of
+    /// `--retry-status` and `--retry-code`. Only the last attempt counts as
+    /// an error.
+    #[arg(long, default_value = "1")]
+    max_attempts: NonZeroU32,
+    /// Milliseconds to back off before the first retry, doubling with every
+    /// further one
+    ///
+    /// Every backoff is drawn uniformly from zero up to that, so that
+    /// workers don't retry in lockstep
+    #[arg(long, default_value = "50")]
+    retry_backoff: u64,
+    /// Milliseconds to back off at most before a retry
+    #[arg(long, default_value = "1000")]
+    retry_backoff_max: u64,
+    /// Retry HTTP responses with this status code, may be repeated
+    #[arg(long, default_values = ["429", "502", "503", "504"])]
+    retry_status: Vec<reqwest::StatusCode>,
+    /// Retry gRPC responses with this status code, may be repeated
+    #[arg(long, value_enum, default_values = ["unavailable"])]
+    retry_code: Vec<GrpcCode>,
     /// Run for this amount of seconds before starting to measure
+    ///
+    /// With `--warm-up-requests` or `--warm-up-stable`, this is the longest
+    /// the warm-up may take
     #[arg(long, default_value = "5")]
     warm_up: u64,
+    /// End the warm-up once this many requests completed, across all
+    /// workers
+    #[arg(long, conflicts_with = "warm_up_stable")]
+    warm_up_requests: Option<NonZeroU64>,
+    /// End the warm-up once the p50 latency stays within this many percent
+    /// over `--warm-up-windows` consecutive windows
+    #[arg(long, value_parser = positive_finite)]
+    warm_up_stable: Option<f64>,
+    /// How many consecutive windows the p50 latency must be stable for
+    #[arg(long, default_value = "3")]
+    warm_up_windows: NonZeroUsize,
+    /// Milliseconds per window for `--warm-up-stable`
+    #[arg(long, default_value = "1000")]
+    warm_up_window: NonZeroU64,
+    /// Don't show live progress
+    ///
+    /// Progress is only shown when stderr is a terminal
+    #[arg(long)]
+    no_progress: bool,
+}
+
+#[derive(Debug, Clone, ValueEnum, Eq, PartialEq, Copy)]
+enum Mode {
+    /// Send requests at a fixed rate, regardless of how fast responses arrive
+    Open,
+    /// Like open mode, but every worker keeps its own rate, as independent
+    /// clients would
+    PerWorker,
+    /// Have every worker send requests back-to-back, without a rate limit
+    Closed,
+}
+
+#[derive(Debug, Clone, ValueEnum, Eq, PartialEq, Copy)]
+enum Arrival {
+    /// Evenly spaced, up to `--jitter` apart from that
+    Uniform,
+    /// With exponentially distributed times in between, as independent
+    /// clients would
+    Poisson,
+    /// Evenly spaced during bursts, none in between, at the same rate on
+    /// average
+    Bursty,
+}
+
+#[derive(Debug, Clone, ValueEnum, Eq, PartialEq, Copy)]
+enum GrpcCode {
+    Cancelled,
+    Unknown,
+    InvalidArgument,
+    DeadlineExceeded,
+    NotFound,
+    AlreadyExists,
+    PermissionDenied,
+    ResourceExhausted,
+    FailedPrecondition,
+    Aborted,
+    OutOfRange,
+    Unimplemented,
+    Internal,
+    Unavailable,
+    DataLoss,
+    Unauthenticated,
+}
+
+impl From<GrpcCode> for tonic::Code {
+    fn from(code: GrpcCode) -> Self {
+        match code {
+            GrpcCode::Cancelled => Self::Cancelled,
+            GrpcCode::Unknown => Self::Unknown,
+            GrpcCode::InvalidArgument => Self::InvalidArgument,
+            GrpcCode::DeadlineExceeded => Self::DeadlineExceeded,
+            GrpcCode::NotFound => Self::NotFound,
+            GrpcCode::AlreadyExists => Self::AlreadyExists,
+            GrpcCode::PermissionDenied => Self::PermissionDenied,
+            GrpcCode::ResourceExhausted => Self::ResourceExhausted,
+            GrpcCode::FailedPrecondition => Self::FailedPrecondition,
+            GrpcCode::Aborted => Self::Aborted,
+            GrpcCode::OutOfRange => Self::OutOfRange,
+            GrpcCode::Unimplemented => Self::Unimplemented,
+            GrpcCode::Internal => Self::Internal,
+            GrpcCode::Unavailable => Self::Unavailable,
+            GrpcCode::DataLoss => Self::DataLoss,
+            GrpcCode::Unauthenticated => Self::Unauthenticated,
+        }
+    }
+}
+
+#[derive(Debug, Clone, ValueEnum, Eq, PartialEq, Copy)]
+enum Distribution {
+    /// Always the mean
+    Constant,
+    /// Uniformly between zero and twice the mean
+    Uniform,
+    /// Exponentially, as between independent events
+    Exponential,
 }
 
 #[derive(Debug, Args)]
@@ -80,6 +312,30 @@ struct Client {
     /// Where to send requests
     #[arg(long, default_value = "127.0.0.1")]
     hostname: String,
+    /// The authority to address requests to, as `host` or `host:port`
+    ///
+    /// Defaults to `--hostname` and the port of the client
+    #[arg(long)]
+    authority: Option<Authority>,
+    /// Open connections to this `host:port` instead of the authority, such as
+    /// a sidecar or load balancer
+    ///
+    /// HTTP clients need a host name rather than an IP address in
+    /// `--authority` for this, with the same port or none.
+    #[arg(long)]
+    connect_to: Option<Authority>,
+    /// Send requests through this HTTP proxy, as `http://host:port`
+    ///
+    /// HTTP clients send requests to it as to a forward proxy, gRPC clients
+    /// and the connect client tunnel with `CONNECT`.
+    #[arg(long)]
+    proxy: Option<Uri>,
+    /// Connect to the Unix domain socket at this path instead of over TCP
+    ///
+    /// Requests are still addressed to the authority. Not supported by the
+    /// HTTP client.
+    #[arg(long, conflicts_with_all = ["connect_to", "proxy"])]
+    uds: Option<PathBuf>,
 }
 
 #[derive(Debug, Subcommand)]
@@ -88,6 +344,16 @@ enum ClientType {
     Grpc(Grpc),
     /// Run as HTTP/REST client
     Rest(Rest),
+    /// Run as gRPC client for any unary method, described by protobuf
+    /// descriptors loaded at runtime
+    GrpcDynamic(GrpcDynamic),
+    /// Run as HTTP client for any endpoint, sending templated requests
+    Http(Http),
+    /// Open a new connection for every request, timing the handshake and
+    /// the first request separately
+    Connect(Connect),
+    /// Run as WebSocket client, sending requests over one socket per worker
+    Websocket(Websocket),
 }
 
 #[derive(Debug, Args)]
@@ -95,6 +361,25 @@ struct Grpc {
     /// Which port to send requests to
     #[arg(long, default_value = "55556")]
     port: u16,
+    /// Wait up to this many seconds for the server to report itself as
+    /// healthy before starting
+    #[arg(long)]
+    wait_healthy: Option<u64>,
+    /// Request metadata as `key=value`, may be repeated
+    ///
+    /// The value may contain placeholders that are substituted for every
+    /// request: `{{seq}}`, `{{int}}`, `{{int:LOW..HIGH}}`, `{{word}}`,
+    /// `{{words:N}}` and `{{uuid}}`
+    #[arg(long)]
+    metadata: Vec<client::grpc::Metadata>,
+    /// Time response headers, the response message and decoding it
+    /// separately
+    ///
+    /// Responses are then received like those of a server streaming call,
+    /// which is the same on the wire but not the same code path as a unary
+    /// call.
+    #[arg(long)]
+    breakdown: bool,
 }
 
 #[derive(Debug, Args)]
@@ -102,15 +387,178 @@ struct Rest {
     /// Which port to send requests to
     #[arg(long, default_value = "55555")]
     port: u16,
+    /// A request header as `name: value`, may be repeated
+    ///
+    /// The value may contain placeholders that are substituted for every
+    /// request: `{{seq}}`, `{{int}}`, `{{int:LOW..HIGH}}`, `{{word}}`,
+    /// `{{words:N}}` and `{{uuid}}`
+    #[arg(long)]
+    header: Vec<client::http::Header>,
+    /// Send requests over HTTP/3 on QUIC instead of HTTP/2, to a server
+    /// started with `--addr-h3`
+    ///
+    /// The server's certificate is not verified.
+    #[arg(long)]
+    http3: bool,
+}
+
+#[derive(Debug, Args)]
+#[group(id = "descriptors", required = true, args = ["proto", "descriptor_set"])]
+struct GrpcDynamic {
+    /// Which port to send requests to
+    #[arg(long, default_value = "55556")]
+    port: u16,
+    /// `.proto` file describing the service, may be repeated
+    #[arg(long)]
+    proto: Vec<PathBuf>,
+    /// Directory to search for imports in, may be repeated
+    ///
+    /// Defaults to the directories of the `.proto` files
+    #[arg(long, requires = "proto")]
+    include: Vec<PathBuf>,
+    /// Serialized `FileDescriptorSet` describing the service, as an
+    /// alternative to `--proto`
+    #[arg(long, conflicts_with = "proto")]
+    descriptor_set: Option<PathBuf>,
+    /// The method to call, as `package.Service/Method`
+    #[arg(long)]
+    method: String,
+    /// The request message as JSON
+    ///
+    /// May contain placeholders that are substituted for every request:
+    /// `{{seq}}`, `{{int}}`, `{{int:LOW..HIGH}}`, `{{word}}`,
+    /// `{{words:N}}` and `{{uuid}}`
+    #[arg(long, default_value = "{}")]
+    request: Template,
+    /// Request metadata as `key=value`, may be repeated
+    ///
+    /// The value may contain placeholders
+    #[arg(long)]
+    metadata: Vec<client::grpc::Metadata>,
+    /// Wait up to this many seconds for the server to report the service as
+    /// healthy before starting
+    #[arg(long)]
+    wait_healthy: Option<u64>,
+}
+
+#[derive(Debug, Args)]
+struct Http {
+    /// Which port to send requests to
+    #[arg(long, default_value = "55555")]
+    port: u16,
+    /// Use HTTP/1.1 instead of HTTP/2
+    #[arg(long)]
+    http1: bool,
+    /// The request method
+    #[arg(long, default_value = "GET")]
+    method: reqwest::Method,
+    /// The request path, may contain placeholders
+    ///
+    /// Placeholders are substituted for every request: `{{seq}}`,
+    /// `{{int}}`, `{{int:LOW..HIGH}}`, `{{word}}`, `{{words:N}}` and
+    /// `{{uuid}}`
+    #[arg(long, default_value = "/")]
+    path: Template,
+    /// A request header as `name: value`, may be repeated
+    ///
+    /// The value may contain placeholders
+    #[arg(long)]
+    header: Vec<client::http::Header>,
+    /// The request body, may contain placeholders
+    #[arg(long)]
+    body: Option<Template>,
+    /// Count responses with any other status code as errors, may be
+    /// repeated
+    ///
+    /// By default, every status code is accepted
+    #[arg(long)]
+    expect_status: Vec<reqwest::StatusCode>,
+}
+
+#[derive(Debug, Args)]
+struct Connect {
+    /// The protocol to connect with
+    #[arg(long, value_enum)]
+    protocol: Protocol,
+    /// Which port to connect to
+    ///
+    /// Defaults to 55556 for gRPC and 55555 for HTTP/REST
+    #[arg(long)]
+    port: Option<u16>,
 }
 
 #[derive(Debug, Clone, ValueEnum, Eq, PartialEq, Copy)]
+enum Protocol {
+    Grpc,
+    Rest,
+}
+
+#[derive(Debug, Args)]
+struct Websocket {
+    /// Which port to send requests to
+    #[arg(long, default_value = "55555")]
+    port: u16,
+    /// How requests and responses are encoded in frames
+    #[arg(long, value_enum, default_value = "json")]
+    format: FrameFormat,
+}
+
+#[derive(Debug, Clone, ValueEnum, Eq, PartialEq, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum FrameFormat {
+    /// JSON in text frames
+    #[default]
+    Json,
+    /// Protobuf in binary frames
+    Protobuf,
+}
+
+#[derive(Debug, Args)]
+struct Codec {
+    /// The type of workload
+    #[arg(long, value_enum)]
+    workload: Workload,
+    /// Which encoding to measure, may be repeated
+    ///
+    /// Defaults to all of them
+    #[arg(long, value_enum)]
+    encoding: Vec<Encoding>,
+    /// How many different payloads to generate and cycle through
+    #[arg(long, default_value = "1000")]
+    payloads: NonZeroUsize,
+    /// How many seconds to measure each encoding for
+    #[arg(long, default_value = "5")]
+    duration: u64,
+}
+
+#[derive(Debug, Clone, ValueEnum, Eq, PartialEq, Copy)]
+enum Encoding {
+    /// JSON with `serde_json`
+    SerdeJson,
+    /// JSON with `simd-json`
+    SimdJson,
+    /// Protobuf with `prost`
+    Prost,
+}
+
+#[derive(Debug, Clone, ValueEnum, Eq, PartialEq, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 enum Workload {
     Inty,
     Stringy,
     Mixed,
 }
 
+/// Parse a number that is greater than zero and finite.
+fn positive_finite(s: &str) -> anyhow::Result<f64> {
+    let value: f64 = s.parse()?;
+    ensure!(
+        value.is_finite() && value > 0.0,
+        "expected a positive number, got `{s}`"
+    );
+    Ok(value)
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let _ = dotenvy::dotenv();
@@ -119,36 +567,241 @@ async fn main() -> anyhow::Result<()> {
     info!(?cli);
 
     match cli.program {
-        Program::Server(Server {
-            addr_http,
-            addr_grpc,
-        }) => {
-            let http = tokio::spawn(async move { server::run_http(&addr_http).await });
-            let grpc = tokio::spawn(async move { server::run_grpc(&addr_grpc).await });
-            let (http, grpc) = try_join(http, grpc).await?;
-            http?;
-            grpc?;
-        }
-        Program::Client(Client {
-            r#type: type_,
-            hostname,
-            bench,
-        }) => {
-            let report = match type_ {
-                ClientType::Grpc(Grpc { port }) => {
-                    let c = client::grpc::Client::connect(format!("http://{hostname}:{port}"))
-                        .await
-                        .context("grpc connect")?;
-                    benchmark(c, bench).await.context("benchmark")?
-                }
-                ClientType::Rest(Rest { port }) => {
-                    let c = client::rest::Client::new(&format!("http://{hostname}:{port}"));
-                    benchmark(c, bench).await.context("benchmark")?
-                }
-            };
+        Program::Server(server) => run_server(server).await,
+        Program::Client(client) => run_client(client).await,
+        Program::Codec(codec) => {
+            let report = codec::benchmark(codec).context("benchmark")?;
             println!("{report}");
+            Ok(())
         }
     }
+}
+
+async fn run_server(
+    Server {
+        addr_http,
+        addr_grpc,
+        addr,
+        addr_h3,
+        uds_http,
+        uds_grpc,
+        drain_timeout,
+        payload_mode,
+        pool_size,
+        seed,
+    }: Server,
+) -> anyhow::Result<()> {
+    let state = Arc::new(server::AppState {
+        stats: server::Stats::default(),
+        payloads: server::Payloads::new(payload_mode, pool_size, seed),
+    });
+    let shutdown = CancellationToken::new();
+    let mut servers = tokio::spawn({
+        let state = state.clone();
+        let shutdown = shutdown.clone();
+        async move {
+            let mut listeners: Vec<BoxFuture<anyhow::Result<()>>> = Vec::new();
+            if let Some(addr) = &addr {
+                listeners.push(Box::pin(server::run_multiplexed(
+                    addr,
+                    state.clone(),
+                    shutdown.clone(),
+                )));
+            } else {
+                listeners.push(Box::pin(server::run_http(
+                    &addr_http,
+                    state.clone(),
+                    shutdown.clone(),
+                )));
+                listeners.push(Box::pin(server::run_grpc(
+                    &addr_grpc,
+                    state.clone(),
+                    shutdown.clone(),
+                )));
+            }
+            if let Some(addr) = &addr_h3 {
+                listeners.push(Box::pin(server::run_http3(
+                    addr,
+                    state.clone(),
+                    shutdown.clone(),
+                )));
+            }
+            if let Some(path) = &uds_http {
+                listeners.push(Box::pin(server::run_http_unix(
+                    path,
+                    state.clone(),
+                    shutdown.clone(),
+                )));
+            }
+            if let Some(path) = &uds_grpc {
+                listeners.push(Box::pin(server::run_grpc_unix(path, state, shutdown)));
+            }
+            try_join_all(listeners).await.map(drop)
+        }
+    });
+    let joined = tokio::select! {
+        joined = &mut servers => Some(joined),
+        () = server::shutdown_signal() => {
+            info!("Shutting down, draining in-flight requests for up to {drain_timeout} s");
+            shutdown.cancel();
+            tokio::time::timeout(Duration::from_secs(drain_timeout), &mut servers)
+                .await
+                .inspect_err(|_elapsed| warn!("Drain deadline exceeded"))
+                .ok()
+        },
+    };
+    state.stats.log_summary();
+    if let Some(joined) = joined {
+        joined??;
+    }
+    Ok(())
+}
 
+#[allow(clippy::too_many_lines)]
+async fn run_client(
+    Client {
+        r#type: type_,
+        hostname,
+        authority,
+        connect_to,
+        proxy,
+        uds,
+        bench,
+    }: Client,
+) -> anyhow::Result<()> {
+    let route = move |port: u16| {
+        let authority = match authority {
+            Some(authority) => authority,
+            None => format!("{hostname}:{port}")
+                .parse()
+                .context("--hostname is no valid host")?,
+        };
+        Route::new(authority, connect_to, proxy, uds)
+    };
+    ensure!(
+        matches!(
+            type_,
+            ClientType::Grpc(_) | ClientType::Rest(_) | ClientType::Websocket(_)
+        ) || (!bench.validate && bench.seed.is_none()),
+        "--validate and --seed only apply to the grpc, rest and websocket clients"
+    );
+    let report = match type_ {
+        ClientType::Grpc(Grpc {
+            port,
+            wait_healthy,
+            metadata,
+            breakdown,
+        }) => {
+            let workload = bench.workload.context("--workload is required")?;
+            let route = route(port)?;
+            if let Some(timeout) = wait_healthy {
+                client::grpc::wait_healthy(
+                    &route,
+                    proto::battlebots_service_server::SERVICE_NAME,
+                    Duration::from_secs(timeout),
+                )
+                .await?;
+            }
+            let c = client::grpc::Client::connect(&route, metadata, breakdown)
+                .await
+                .context("grpc connect")?;
+            benchmark(
+                Builtin::new(c, workload, bench.validate, bench.seed),
+                bench,
+                &route,
+            )
+            .await
+            .context("benchmark")?
+        }
+        ClientType::Rest(Rest {
+            port,
+            header,
+            http3,
+        }) => {
+            let workload = bench.workload.context("--workload is required")?;
+            let mut route = route(port)?;
+            if http3 {
+                route = route.over_quic()?;
+            }
+            let c = client::rest::Client::new(&route, header).await?;
+            benchmark(
+                Builtin::new(c, workload, bench.validate, bench.seed),
+                bench,
+                &route,
+            )
+            .await
+            .context("benchmark")?
+        }
+        ClientType::GrpcDynamic(GrpcDynamic {
+            port,
+            proto,
+            include,
+            descriptor_set,
+            method,
+            request,
+            metadata,
+            wait_healthy,
+        }) => {
+            let pool =
+                client::dynamic::load_descriptors(&proto, &include, descriptor_set.as_deref())
+                    .context("loading descriptors")?;
+            let route = route(port)?;
+            let c = client::dynamic::Client::connect(
+                &route,
+                &pool,
+                &method,
+                request,
+                metadata,
+                wait_healthy.map(Duration::from_secs),
+            )
+            .await
+            .context("grpc connect")?;
+            benchmark(c, bench, &route).await.context("benchmark")?
+        }
+        ClientType::Http(Http {
+            port,
+            http1,
+            method,
+            path,
+            header,
+            body,
+            expect_status,
+        }) => {
+            let route = route(port)?;
+            let c = client::http::Client::new(
+                &route,
+                http1,
+                method,
+                path,
+                header,
+                body,
+                expect_status,
+            )?;
+            benchmark(c, bench, &route).await.context("benchmark")?
+        }
+        ClientType::Connect(Connect { protocol, port }) => {
+            let workload = bench.workload.context("--workload is required")?;
+            let port = port.unwrap_or(match protocol {
+                Protocol::Grpc => 55556,
+                Protocol::Rest => 55555,
+            });
+            let route = route(port)?;
+            let c = client::connect::Client::new(protocol, route.clone(), workload);
+            benchmark(c, bench, &route).await.context("benchmark")?
+        }
+        ClientType::Websocket(Websocket { port, format }) => {
+            let workload = bench.workload.context("--workload is required")?;
+            let route = route(port)?;
+            let c = client::websocket::Client::new(route.clone(), format);
+            benchmark(
+                Builtin::new(c, workload, bench.validate, bench.seed),
+                bench,
+                &route,
+            )
+            .await
+            .context("benchmark")?
+        }
+    };
+    println!("{report}");
     Ok(())
 }
@@ -1,2 +1,4 @@
 #![allow(clippy::pedantic)]
 tonic::include_proto!("battlebots");
+
+pub const FILE_DESCRIPTOR_SET: &[u8] = tonic::include_file_descriptor_set!("battlebots_descriptor");
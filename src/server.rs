@@ -1,30 +1,255 @@
+use std::future::Future;
 use std::net::SocketAddr;
+use std::os::unix::fs::FileTypeExt;
+use std::path::Path;
+use std::sync::Arc;
 
+use anyhow::Context;
+use axum::Router;
+use axum::ServiceExt;
+use hyper_util::rt::TokioExecutor;
+use hyper_util::rt::TokioIo;
+use hyper_util::server::conn::auto;
+use hyper_util::server::graceful::GracefulShutdown;
+use hyper_util::service::TowerToHyperService;
+use tokio::net::UnixListener;
+use tokio_util::sync::CancellationToken;
+use tonic::service::Routes;
+use tonic_health::ServingStatus;
+use tonic_health::server::HealthReporter;
+use tracing::debug;
 use tracing::info;
 use tracing::instrument;
 
+pub use self::payloads::Payloads;
+pub use self::stats::Stats;
+use crate::PayloadMode;
 use crate::proto::battlebots_service_server::BattlebotsServiceServer;
+use crate::proto::battlebots_service_server::SERVICE_NAME;
 
 pub mod grpc;
+mod http3;
+mod multiplex;
+mod payloads;
 pub mod rest;
+mod stats;
+mod websocket;
 
-#[instrument]
-pub async fn run_http(addr: &SocketAddr) -> anyhow::Result<()> {
-    let router = rest::router();
+const REFLECTION_SERVICE_NAME: &str = "grpc.reflection.v1.ServerReflection";
+
+/// State shared by all request handlers.
+pub struct AppState {
+    pub stats: Stats,
+    pub payloads: Payloads,
+}
+
+/// Wait for SIGINT (Ctrl-C) or SIGTERM.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to listen for ctrl-c");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        () = ctrl_c => info!("Received SIGINT"),
+        () = terminate => info!("Received SIGTERM"),
+    }
+}
+
+/// Serve the HTTP/REST API until `shutdown` is cancelled, then stop accepting
+/// connections and wait for in-flight requests to finish.
+#[instrument(skip(state, shutdown))]
+pub async fn run_http(
+    addr: &SocketAddr,
+    state: Arc<AppState>,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let router = rest::router(state);
     let listener = tokio::net::TcpListener::bind(&addr).await?;
     info!("listening");
-    axum::serve(listener, router).await?;
+    axum::serve(listener, router)
+        .with_graceful_shutdown(shutdown.cancelled_owned())
+        .await?;
+    info!("stopped");
     Ok(())
 }
 
-#[instrument]
-pub async fn run_grpc(addr: &SocketAddr) -> anyhow::Result<()> {
-    let service = grpc::BattlebotsService;
-    let server = BattlebotsServiceServer::new(service);
+/// Serve the gRPC API until `shutdown` is cancelled, then stop accepting
+/// connections and wait for in-flight requests to finish.
+#[instrument(skip(state, shutdown))]
+pub async fn run_grpc(
+    addr: &SocketAddr,
+    state: Arc<AppState>,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let (routes, health_reporter) = grpc_routes(state).await?;
     info!("listening");
     tonic::transport::Server::builder()
-        .add_service(server)
-        .serve(*addr)
+        .add_routes(routes)
+        .serve_with_shutdown(*addr, report_not_serving(health_reporter, shutdown))
         .await?;
+    info!("stopped");
+    Ok(())
+}
+
+/// Serve both the HTTP/REST and the gRPC API on a single listener, telling
+/// them apart by `content-type`.
+///
+/// Shuts down like [`run_http`] and [`run_grpc`].
+#[instrument(skip(state, shutdown))]
+pub async fn run_multiplexed(
+    addr: &SocketAddr,
+    state: Arc<AppState>,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let (routes, health_reporter) = grpc_routes(state.clone()).await?;
+    let multiplexer = multiplex::Multiplexer {
+        rest: rest::router(state),
+        grpc: routes.into_axum_router(),
+    };
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    info!("listening");
+    // As tonic does, so that gRPC isn't held up by Nagle's algorithm
+    axum::serve(listener, multiplexer.into_make_service())
+        .tcp_nodelay(true)
+        .with_graceful_shutdown(report_not_serving(health_reporter, shutdown))
+        .await?;
+    info!("stopped");
+    Ok(())
+}
+
+/// Serve the HTTP/REST API over HTTP/3 on the UDP socket `addr`, with a
+/// self-signed certificate.
+///
+/// Shuts down like [`run_http`].
+#[instrument(skip(state, shutdown))]
+pub async fn run_http3(
+    addr: &SocketAddr,
+    state: Arc<AppState>,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    http3::serve(addr, rest::router(state), shutdown).await
+}
+
+/// Serve the HTTP/REST API on a Unix domain socket at `path`.
+///
+/// Shuts down like [`run_http`].
+#[instrument(skip(state, shutdown))]
+pub async fn run_http_unix(
+    path: &Path,
+    state: Arc<AppState>,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    serve_unix(path, rest::router(state), shutdown.cancelled_owned()).await
+}
+
+/// Serve the gRPC API on a Unix domain socket at `path`.
+///
+/// Shuts down like [`run_grpc`].
+#[instrument(skip(state, shutdown))]
+pub async fn run_grpc_unix(
+    path: &Path,
+    state: Arc<AppState>,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let (routes, health_reporter) = grpc_routes(state).await?;
+    serve_unix(
+        path,
+        routes.into_axum_router(),
+        report_not_serving(health_reporter, shutdown),
+    )
+    .await
+}
+
+/// Serve `router` on a Unix domain socket at `path` until `shutdown`
+/// completes, then wait for in-flight requests to finish and remove the
+/// socket.
+async fn serve_unix(
+    path: &Path,
+    router: Router,
+    shutdown: impl Future<Output = ()>,
+) -> anyhow::Result<()> {
+    // A socket left behind by an earlier run would keep us from binding
+    if std::fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+        std::fs::remove_file(path).with_context(|| format!("removing {}", path.display()))?;
+    }
+    let listener =
+        UnixListener::bind(path).with_context(|| format!("binding {}", path.display()))?;
+    info!("listening");
+    let graceful = GracefulShutdown::new();
+    tokio::pin!(shutdown);
+    loop {
+        let stream = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _addr)) => stream,
+                Err(error) => {
+                    debug!(%error, "accepting connection");
+                    continue;
+                }
+            },
+            () = &mut shutdown => break,
+        };
+        let connection = auto::Builder::new(TokioExecutor::new())
+            .serve_connection_with_upgrades(
+                TokioIo::new(stream),
+                TowerToHyperService::new(router.clone()),
+            )
+            .into_owned();
+        let connection = graceful.watch(connection);
+        tokio::spawn(async move {
+            if let Err(error) = connection.await {
+                debug!(%error, "serving connection");
+            }
+        });
+    }
+    graceful.shutdown().await;
+    std::fs::remove_file(path).with_context(|| format!("removing {}", path.display()))?;
+    info!("stopped");
     Ok(())
 }
+
+/// The gRPC API along with reflection and health checking.
+async fn grpc_routes(state: Arc<AppState>) -> anyhow::Result<(Routes, HealthReporter)> {
+    let reflection = tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(crate::proto::FILE_DESCRIPTOR_SET)
+        .register_encoded_file_descriptor_set(tonic_health::pb::FILE_DESCRIPTOR_SET)
+        .build_v1()?;
+    let (mut health_reporter, health) = tonic_health::server::health_reporter();
+    health_reporter
+        .set_service_status(SERVICE_NAME, ServingStatus::Serving)
+        .await;
+    health_reporter
+        .set_service_status(REFLECTION_SERVICE_NAME, ServingStatus::Serving)
+        .await;
+    let routes = Routes::new(health).add_service(reflection);
+    let counter = state.stats.grpc.clone();
+    let routes = match state.payloads.mode {
+        PayloadMode::Random | PayloadMode::Pool => routes.add_service(grpc::Tracking::new(
+            BattlebotsServiceServer::new(grpc::BattlebotsService { state }),
+            counter,
+        )),
+        PayloadMode::Fixed => routes.add_service(grpc::Tracking::new(
+            grpc::FixedBattlebotsService { state },
+            counter,
+        )),
+    };
+    Ok((routes, health_reporter))
+}
+
+/// Wait for `shutdown`, then tell health checking clients that the service
+/// is going away.
+async fn report_not_serving(mut health_reporter: HealthReporter, shutdown: CancellationToken) {
+    shutdown.cancelled().await;
+    health_reporter
+        .set_service_status(SERVICE_NAME, ServingStatus::NotServing)
+        .await;
+}
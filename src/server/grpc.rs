@@ -1,19 +1,48 @@
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+
+use bytes::BufMut;
+use bytes::Bytes;
+use prost::Message;
 use tonic::Request;
 use tonic::Response;
 use tonic::Status;
+use tonic::body::BoxBody;
+use tonic::codec::Codec;
+use tonic::codec::DecodeBuf;
+use tonic::codec::Decoder;
+use tonic::codec::EncodeBuf;
+use tonic::codec::Encoder;
+use tonic::codegen::BoxFuture;
+use tonic::codegen::http;
+use tonic::server::NamedService;
+use tonic::server::UnaryService;
+use tower_service::Service;
 
+use super::AppState;
+use super::payloads::Source;
+use super::stats::Counter;
+use super::stats::TrackedBody;
 use crate::proto::Empty;
 use crate::proto::Inty;
 use crate::proto::Mixed;
 use crate::proto::Stringy;
 use crate::proto::battlebots_service_server::BattlebotsService as Svc;
+use crate::proto::battlebots_service_server::SERVICE_NAME;
+use crate::workloads::Rand;
+use crate::workloads::SEED_HEADER;
+use crate::workloads::generate;
 
-pub struct BattlebotsService;
+pub struct BattlebotsService {
+    pub state: Arc<AppState>,
+}
 
 impl From<crate::workloads::stringy::Payload> for Stringy {
     fn from(value: crate::workloads::stringy::Payload) -> Self {
         Self {
-            configuration: value.configuration,
+            configuration: value.configuration.into_iter().collect(),
             body: Some(value.body),
             messages: value.messages,
         }
@@ -23,7 +52,7 @@ impl From<crate::workloads::stringy::Payload> for Stringy {
 impl From<crate::workloads::inty::Payload> for Inty {
     fn from(value: crate::workloads::inty::Payload) -> Self {
         Self {
-            configuration: value.configuration,
+            configuration: value.configuration.into_iter().collect(),
             header: Some(value.header),
             ids: value.ids,
         }
@@ -41,18 +70,214 @@ impl From<crate::workloads::mixed::Payload> for Mixed {
 
 #[tonic::async_trait]
 impl Svc for BattlebotsService {
-    async fn get_stringy(&self, _request: Request<Empty>) -> Result<Response<Stringy>, Status> {
-        let stringy = crate::workloads::stringy::Payload::rand(&mut rand::rng());
-        Ok(Response::new(stringy.into()))
+    async fn get_stringy(&self, request: Request<Empty>) -> Result<Response<Stringy>, Status> {
+        let seed = seed(&request, &self.state)?;
+        let stringy =
+            message::<crate::workloads::stringy::Payload, _>(&self.state.payloads.stringy, seed)
+                .ok_or_else(|| Status::internal("fixed payloads are served pre-encoded"))?;
+        Ok(Response::new(stringy))
+    }
+
+    async fn get_inty(&self, request: Request<Empty>) -> Result<Response<Inty>, Status> {
+        let seed = seed(&request, &self.state)?;
+        let inty = message::<crate::workloads::inty::Payload, _>(&self.state.payloads.inty, seed)
+            .ok_or_else(|| Status::internal("fixed payloads are served pre-encoded"))?;
+        Ok(Response::new(inty))
+    }
+
+    async fn get_mixed(&self, request: Request<Empty>) -> Result<Response<Mixed>, Status> {
+        let seed = seed(&request, &self.state)?;
+        let mixed =
+            message::<crate::workloads::mixed::Payload, _>(&self.state.payloads.mixed, seed)
+                .ok_or_else(|| Status::internal("fixed payloads are served pre-encoded"))?;
+        Ok(Response::new(mixed))
+    }
+}
+
+/// The seed a request asks its payload to be generated from, if any.
+#[allow(clippy::result_large_err)]
+fn seed<T>(request: &Request<T>, state: &AppState) -> Result<Option<u64>, Status> {
+    let seed = request
+        .metadata()
+        .get(SEED_HEADER)
+        .map(|value| {
+            value
+                .to_str()
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .ok_or_else(|| {
+                    Status::invalid_argument(format!("`{SEED_HEADER}` must be an unsigned integer"))
+                })
+        })
+        .transpose()?;
+    state
+        .payloads
+        .check_seed(seed.is_some())
+        .map_err(Status::invalid_argument)?;
+    Ok(seed)
+}
+
+/// The next message from `source`, unless the payloads are fixed. A `seed`
+/// from the request, which only random mode accepts, takes precedence.
+fn message<T, P>(source: &Source<T, P>, seed: Option<u64>) -> Option<P>
+where
+    T: Rand + Into<P>,
+    P: Clone,
+{
+    match source {
+        Source::Random(seeds) => Some(generate::<T>(seed.or_else(|| seeds.next_grpc())).into()),
+        Source::Pool(pool) => Some(pool.next_grpc().clone()),
+        Source::Fixed(_) => None,
+    }
+}
+
+/// Serves the fixed payloads, encoded up front, so that no serialization
+/// happens per request.
+///
+/// Stands in for the generated `BattlebotsServiceServer`, which can only
+/// serve messages it encodes itself.
+#[derive(Clone)]
+pub struct FixedBattlebotsService {
+    pub state: Arc<AppState>,
+}
+
+impl NamedService for FixedBattlebotsService {
+    const NAME: &'static str = SERVICE_NAME;
+}
+
+impl Service<http::Request<BoxBody>> for FixedBattlebotsService {
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<BoxBody>) -> Self::Future {
+        let payloads = &self.state.payloads;
+        if let Err(reason) = payloads.check_seed(request.headers().contains_key(SEED_HEADER)) {
+            let status = Status::invalid_argument(reason);
+            return Box::pin(async { Ok(status.into_http()) });
+        }
+        let encoded = match request
+            .uri()
+            .path()
+            .strip_prefix(&format!("/{SERVICE_NAME}/"))
+        {
+            Some("GetStringy") => fixed(&payloads.stringy),
+            Some("GetInty") => fixed(&payloads.inty),
+            Some("GetMixed") => fixed(&payloads.mixed),
+            _ => return Box::pin(async { Ok(Status::unimplemented("").into_http()) }),
+        };
+        Box::pin(async move {
+            let Some(encoded) = encoded else {
+                let status = Status::internal("only fixed payloads are served pre-encoded");
+                return Ok(status.into_http());
+            };
+            Ok(tonic::server::Grpc::new(PreEncodedCodec)
+                .unary(Respond(encoded), request)
+                .await)
+        })
+    }
+}
+
+/// Counts the calls `S` handles, completing them once their response has
+/// been sent.
+#[derive(Clone)]
+pub struct Tracking<S> {
+    inner: S,
+    counter: Arc<Counter>,
+}
+
+impl<S> Tracking<S> {
+    pub fn new(inner: S, counter: Arc<Counter>) -> Self {
+        Self { inner, counter }
     }
+}
+
+impl<S: NamedService> NamedService for Tracking<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<S> Service<http::Request<BoxBody>> for Tracking<S>
+where
+    S: Service<http::Request<BoxBody>, Response = http::Response<BoxBody>, Error = Infallible>,
+    S::Future: Send + 'static,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<BoxBody>) -> Self::Future {
+        let tracked = self.counter.track();
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            let response = response.await?;
+            Ok(response.map(|body| tonic::body::boxed(TrackedBody::new(body, tracked))))
+        })
+    }
+}
+
+/// The encoded message to respond with, if the payloads are fixed.
+fn fixed<T, P>(source: &Source<T, P>) -> Option<Bytes> {
+    match source {
+        Source::Fixed(fixed) => Some(fixed.protobuf.clone()),
+        Source::Random(_) | Source::Pool(_) => None,
+    }
+}
+
+struct Respond(Bytes);
+
+impl UnaryService<Empty> for Respond {
+    type Response = Bytes;
+    type Future = std::future::Ready<Result<Response<Bytes>, Status>>;
 
-    async fn get_inty(&self, _request: Request<Empty>) -> Result<Response<Inty>, Status> {
-        let inty = crate::workloads::inty::Payload::rand(&mut rand::rng());
-        Ok(Response::new(inty.into()))
+    fn call(&mut self, _request: Request<Empty>) -> Self::Future {
+        std::future::ready(Ok(Response::new(self.0.clone())))
     }
+}
+
+/// Decodes [`Empty`] requests and writes responses that are already encoded
+/// as they are.
+struct PreEncodedCodec;
+
+impl Codec for PreEncodedCodec {
+    type Encode = Bytes;
+    type Decode = Empty;
+    type Encoder = PreEncodedCodec;
+    type Decoder = PreEncodedCodec;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        PreEncodedCodec
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        PreEncodedCodec
+    }
+}
+
+impl Encoder for PreEncodedCodec {
+    type Item = Bytes;
+    type Error = Status;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut EncodeBuf<'_>) -> Result<(), Self::Error> {
+        dst.put(item);
+        Ok(())
+    }
+}
+
+impl Decoder for PreEncodedCodec {
+    type Item = Empty;
+    type Error = Status;
 
-    async fn get_mixed(&self, _request: Request<Empty>) -> Result<Response<Mixed>, Status> {
-        let mixed = crate::workloads::mixed::Payload::rand(&mut rand::rng());
-        Ok(Response::new(mixed.into()))
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Self::Item>, Self::Error> {
+        Empty::decode(src)
+            .map(Some)
+            .map_err(|error| Status::internal(error.to_string()))
     }
 }
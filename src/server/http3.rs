@@ -0,0 +1,125 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use anyhow::Context;
+use axum::Router;
+use axum::body::Body;
+use bytes::Bytes;
+use http::Response;
+use http_body_util::BodyExt;
+use quinn::crypto::rustls::QuicServerConfig;
+use rustls::pki_types::PrivateKeyDer;
+use rustls::pki_types::PrivatePkcs8KeyDer;
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+use tower_service::Service;
+use tracing::debug;
+use tracing::info;
+
+type RequestResolver = h3::server::RequestResolver<h3_quinn::Connection, Bytes>;
+
+/// Serve `router` over HTTP/3 on the UDP socket `addr` until `shutdown` is
+/// cancelled, then wait for in-flight requests to finish.
+///
+/// The certificate is self-signed for `localhost` and the IP address of
+/// `addr`, generated anew on every start.
+pub async fn serve(
+    addr: &SocketAddr,
+    router: Router,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let endpoint = quinn::Endpoint::server(server_config(addr)?, *addr)
+        .with_context(|| format!("binding {addr}"))?;
+    info!("listening");
+    let mut connections = JoinSet::new();
+    loop {
+        let incoming = tokio::select! {
+            incoming = endpoint.accept() => match incoming {
+                Some(incoming) => incoming,
+                None => break,
+            },
+            () = shutdown.cancelled() => break,
+        };
+        let router = router.clone();
+        let shutdown = shutdown.clone();
+        connections.spawn(async move {
+            if let Err(error) = serve_connection(incoming, router, shutdown).await {
+                debug!(%error, "serving connection");
+            }
+        });
+    }
+    connections.join_all().await;
+    endpoint.wait_idle().await;
+    info!("stopped");
+    Ok(())
+}
+
+/// A QUIC server configuration speaking HTTP/3 with a fresh self-signed
+/// certificate.
+fn server_config(addr: &SocketAddr) -> anyhow::Result<quinn::ServerConfig> {
+    let rcgen::CertifiedKey { cert, signing_key } =
+        rcgen::generate_simple_self_signed(vec!["localhost".to_owned(), addr.ip().to_string()])
+            .context("generating self-signed certificate")?;
+    let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(signing_key.serialize_der()));
+    let mut tls = rustls::ServerConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_protocol_versions(&[&rustls::version::TLS13])?
+    .with_no_client_auth()
+    .with_single_cert(vec![cert.der().clone()], key)?;
+    tls.alpn_protocols = vec![b"h3".to_vec()];
+    Ok(quinn::ServerConfig::with_crypto(Arc::new(
+        QuicServerConfig::try_from(tls)?,
+    )))
+}
+
+/// Serve the requests of a single connection until the client closes it or
+/// `shutdown` is cancelled, in which case requests already accepted are
+/// finished before closing.
+async fn serve_connection(
+    incoming: quinn::Incoming,
+    router: Router,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let connection = incoming.await?;
+    let mut h3 = h3::server::Connection::new(h3_quinn::Connection::new(connection.clone())).await?;
+    let mut requests = JoinSet::new();
+    loop {
+        let resolver = tokio::select! {
+            accepted = h3.accept() => match accepted {
+                Ok(Some(resolver)) => resolver,
+                Ok(None) => break,
+                Err(error) if error.is_h3_no_error() => break,
+                Err(error) => return Err(error.into()),
+            },
+            () = shutdown.cancelled() => {
+                h3.shutdown(0).await?;
+                break;
+            }
+        };
+        requests.spawn(respond(resolver, router.clone()));
+    }
+    for responded in requests.join_all().await {
+        if let Err(error) = responded {
+            debug!(%error, "serving request");
+        }
+    }
+    connection.close(h3::error::Code::H3_NO_ERROR.value().try_into()?, b"");
+    Ok(())
+}
+
+/// Read a request, route it and send back the response.
+///
+/// Request bodies are ignored, as no route takes one.
+async fn respond(resolver: RequestResolver, mut router: Router) -> anyhow::Result<()> {
+    let (request, mut stream) = resolver.resolve_request().await?;
+    let response = router.call(request.map(|()| Body::empty())).await?;
+    let (parts, body) = response.into_parts();
+    let body = body.collect().await?.to_bytes();
+    stream
+        .send_response(Response::from_parts(parts, ()))
+        .await?;
+    stream.send_data(body).await?;
+    stream.finish().await?;
+    Ok(())
+}
@@ -0,0 +1,40 @@
+use std::convert::Infallible;
+use std::task::Context;
+use std::task::Poll;
+
+use axum::Router;
+use axum::extract::Request;
+use axum::http::header::CONTENT_TYPE;
+use axum::response::Response;
+use axum::routing::future::RouteFuture;
+use tower_service::Service;
+
+/// Routes gRPC requests, recognized by their `content-type`, to one router
+/// and everything else to another, so both can share a listener.
+#[derive(Clone)]
+pub struct Multiplexer {
+    pub rest: Router,
+    pub grpc: Router,
+}
+
+impl Service<Request> for Multiplexer {
+    type Response = Response;
+    type Error = Infallible;
+    type Future = RouteFuture<Infallible>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let is_grpc = request
+            .headers()
+            .get(CONTENT_TYPE)
+            .is_some_and(|content_type| content_type.as_bytes().starts_with(b"application/grpc"));
+        if is_grpc {
+            self.grpc.call(request)
+        } else {
+            self.rest.call(request)
+        }
+    }
+}
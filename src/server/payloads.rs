@@ -0,0 +1,150 @@
+use std::num::NonZeroUsize;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+use bytes::Bytes;
+use prost::Message;
+use serde::Serialize;
+
+use crate::PayloadMode;
+use crate::proto;
+use crate::workloads::Rand;
+use crate::workloads::generate;
+use crate::workloads::inty;
+use crate::workloads::mixed;
+use crate::workloads::stringy;
+
+/// Where the responses for each workload come from.
+pub struct Payloads {
+    pub mode: PayloadMode,
+    pub inty: Source<inty::Payload, proto::Inty>,
+    pub stringy: Source<stringy::Payload, proto::Stringy>,
+    pub mixed: Source<mixed::Payload, proto::Mixed>,
+}
+
+impl Payloads {
+    /// Prepare payloads for `mode`, generating `pool_size` payloads per
+    /// workload for [`PayloadMode::Pool`].
+    ///
+    /// With a `seed`, the `n`th payload of every workload is generated from
+    /// `seed + n`, in every mode and for both protocols.
+    pub fn new(mode: PayloadMode, pool_size: NonZeroUsize, seed: Option<u64>) -> Self {
+        Self {
+            mode,
+            inty: Source::new(mode, pool_size, seed),
+            stringy: Source::new(mode, pool_size, seed),
+            mixed: Source::new(mode, pool_size, seed),
+        }
+    }
+
+    /// Refuse a request that asks for a `seeded` payload, unless payloads
+    /// are generated for every request. Otherwise the seed couldn't be
+    /// honored.
+    pub fn check_seed(&self, seeded: bool) -> Result<(), &'static str> {
+        if seeded && self.mode != PayloadMode::Random {
+            return Err("seeds are only honored with `--payload-mode random`");
+        }
+        Ok(())
+    }
+}
+
+/// Where the responses for a workload come from, with `T` being the
+/// HTTP/REST payload and `P` the gRPC message.
+pub enum Source<T, P> {
+    /// Generate a new payload for every request
+    Random(Seeds),
+    /// Cycle through pre-generated payloads
+    Pool(Pool<T, P>),
+    /// Serve the same payload, serialized up front
+    Fixed(Fixed),
+}
+
+impl<T, P> Source<T, P>
+where
+    T: Rand + Serialize + Clone + Into<P>,
+    P: Message,
+{
+    fn new(mode: PayloadMode, pool_size: NonZeroUsize, seed: Option<u64>) -> Self {
+        let nth_seed = |n: u64| seed.map(|seed| seed.wrapping_add(n));
+        match mode {
+            PayloadMode::Random => Self::Random(Seeds {
+                seed,
+                next_rest: AtomicU64::new(0),
+                next_grpc: AtomicU64::new(0),
+            }),
+            PayloadMode::Pool => {
+                let rest: Vec<T> = (0..pool_size.get() as u64)
+                    .map(|n| generate(nth_seed(n)))
+                    .collect();
+                let grpc = rest.iter().cloned().map(Into::into).collect();
+                Self::Pool(Pool {
+                    rest,
+                    grpc,
+                    next_rest: AtomicUsize::new(0),
+                    next_grpc: AtomicUsize::new(0),
+                })
+            }
+            PayloadMode::Fixed => {
+                let payload: T = generate(nth_seed(0));
+                let text = serde_json::to_string(&payload).expect("payloads serialize to JSON");
+                let protobuf = payload.into().encode_to_vec();
+                Self::Fixed(Fixed {
+                    json: Bytes::copy_from_slice(text.as_bytes()),
+                    text,
+                    protobuf: protobuf.into(),
+                })
+            }
+        }
+    }
+}
+
+/// Seeds for generating payloads, if the server has a seed. Each protocol
+/// has its own sequence.
+pub struct Seeds {
+    seed: Option<u64>,
+    next_rest: AtomicU64,
+    next_grpc: AtomicU64,
+}
+
+impl Seeds {
+    pub fn next_rest(&self) -> Option<u64> {
+        let seed = self.seed?;
+        Some(seed.wrapping_add(self.next_rest.fetch_add(1, Ordering::Relaxed)))
+    }
+
+    pub fn next_grpc(&self) -> Option<u64> {
+        let seed = self.seed?;
+        Some(seed.wrapping_add(self.next_grpc.fetch_add(1, Ordering::Relaxed)))
+    }
+}
+
+/// Pre-generated payloads, served round-robin. Each protocol has its own
+/// position in the pool.
+pub struct Pool<T, P> {
+    rest: Vec<T>,
+    grpc: Vec<P>,
+    next_rest: AtomicUsize,
+    next_grpc: AtomicUsize,
+}
+
+impl<T, P> Pool<T, P> {
+    pub fn next_rest(&self) -> &T {
+        let index = self.next_rest.fetch_add(1, Ordering::Relaxed);
+        &self.rest[index % self.rest.len()]
+    }
+
+    pub fn next_grpc(&self) -> &P {
+        let index = self.next_grpc.fetch_add(1, Ordering::Relaxed);
+        &self.grpc[index % self.grpc.len()]
+    }
+}
+
+/// A payload serialized as both JSON and protobuf.
+pub struct Fixed {
+    pub json: Bytes,
+    /// The JSON once more, ready for WebSocket text frames, which can't
+    /// share `json`
+    pub text: String,
+    pub protobuf: Bytes,
+}
@@ -1,32 +1,113 @@
+use std::sync::Arc;
+
 use axum::Json;
 use axum::Router;
+use axum::async_trait;
+use axum::body::Body;
+use axum::extract::FromRequestParts;
+use axum::extract::Request;
+use axum::extract::State;
+use axum::http::HeaderValue;
+use axum::http::StatusCode;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::request::Parts;
+use axum::middleware;
+use axum::middleware::Next;
+use axum::response::IntoResponse;
+use axum::response::Response;
 use axum::routing::get;
+use serde::Serialize;
 
+use super::AppState;
+use super::payloads::Source;
+use super::stats::TrackedBody;
+use crate::workloads::Rand;
+use crate::workloads::SEED_HEADER;
+use crate::workloads::generate;
 use crate::workloads::inty::Payload as Inty;
 use crate::workloads::mixed::Payload as Mixed;
 use crate::workloads::stringy::Payload as Stringy;
 
-pub fn router() -> Router {
+pub fn router(state: Arc<AppState>) -> Router {
     Router::new()
         .route("/inty", get(inty))
         .route("/stringy", get(stringy))
         .route("/mixed", get(mixed))
+        .route_layer(middleware::from_fn_with_state(state.clone(), track))
+        .route("/ws", get(super::websocket::upgrade))
+        .with_state(state)
+}
+
+/// Count a request as completed once its response has been sent.
+async fn track(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let tracked = state.stats.http.track();
+    next.run(request)
+        .await
+        .map(|body| Body::new(TrackedBody::new(body, tracked)))
+}
+
+async fn inty(State(state): State<Arc<AppState>>, Seed(seed): Seed) -> Response {
+    respond::<Inty, _>(&state.payloads.inty, seed)
 }
 
-async fn inty() -> Json<Inty> {
-    let mut rng = rand::rng();
-    let payload = Inty::rand(&mut rng);
-    Json(payload)
+async fn stringy(State(state): State<Arc<AppState>>, Seed(seed): Seed) -> Response {
+    respond::<Stringy, _>(&state.payloads.stringy, seed)
 }
 
-async fn stringy() -> Json<Stringy> {
-    let mut rng = rand::rng();
-    let payload = Stringy::rand(&mut rng);
-    Json(payload)
+async fn mixed(State(state): State<Arc<AppState>>, Seed(seed): Seed) -> Response {
+    respond::<Mixed, _>(&state.payloads.mixed, seed)
 }
 
-async fn mixed() -> Json<Mixed> {
-    let mut rng = rand::rng();
-    let payload = Mixed::rand(&mut rng);
-    Json(payload)
+/// Respond with the next payload from `source`. A `seed` from the request,
+/// which only random mode accepts, takes precedence.
+fn respond<T, P>(source: &Source<T, P>, seed: Option<u64>) -> Response
+where
+    T: Rand + Serialize,
+{
+    match source {
+        Source::Random(seeds) => {
+            Json(generate::<T>(seed.or_else(|| seeds.next_rest()))).into_response()
+        }
+        Source::Pool(pool) => Json(pool.next_rest()).into_response(),
+        Source::Fixed(fixed) => (
+            [(CONTENT_TYPE, HeaderValue::from_static("application/json"))],
+            fixed.json.clone(),
+        )
+            .into_response(),
+    }
+}
+
+/// The seed a request asks its payload to be generated from, if any.
+struct Seed(Option<u64>);
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for Seed {
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let seed = parts
+            .headers
+            .get(SEED_HEADER)
+            .map(|value| {
+                value
+                    .to_str()
+                    .ok()
+                    .and_then(|value| value.parse().ok())
+                    .ok_or_else(|| {
+                        (
+                            StatusCode::BAD_REQUEST,
+                            format!("`{SEED_HEADER}` must be an unsigned integer"),
+                        )
+                    })
+            })
+            .transpose()?;
+        state
+            .payloads
+            .check_seed(seed.is_some())
+            .map_err(|reason| (StatusCode::BAD_REQUEST, reason.to_owned()))?;
+        Ok(Seed(seed))
+    }
 }
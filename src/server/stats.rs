@@ -0,0 +1,134 @@
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::task::Context;
+use std::task::Poll;
+use std::time::Instant;
+
+use hyper::body::Body;
+use hyper::body::Frame;
+use hyper::body::SizeHint;
+use tracing::info;
+
+/// Counts the requests handled by the server, for the summary logged at
+/// shutdown.
+pub struct Stats {
+    begin: Instant,
+    pub http: Arc<Counter>,
+    pub grpc: Arc<Counter>,
+    pub websocket: Arc<Counter>,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self {
+            begin: Instant::now(),
+            http: Arc::default(),
+            grpc: Arc::default(),
+            websocket: Arc::default(),
+        }
+    }
+}
+
+impl Stats {
+    pub fn log_summary(&self) {
+        let (http_completed, http_interrupted) = self.http.get();
+        let (grpc_completed, grpc_interrupted) = self.grpc.get();
+        let (websocket_completed, websocket_interrupted) = self.websocket.get();
+        info!(
+            uptime_s = self.begin.elapsed().as_secs(),
+            http_completed,
+            http_interrupted,
+            grpc_completed,
+            grpc_interrupted,
+            websocket_completed,
+            websocket_interrupted,
+            "Server summary"
+        );
+    }
+}
+
+#[derive(Default)]
+pub struct Counter {
+    started: AtomicU64,
+    completed: AtomicU64,
+}
+
+impl Counter {
+    /// Count a request as started. It is counted as interrupted unless
+    /// [`Tracked::complete`] is called.
+    pub fn track(self: &Arc<Self>) -> Tracked {
+        self.started.fetch_add(1, Ordering::Relaxed);
+        Tracked(self.clone())
+    }
+
+    /// Number of completed and interrupted requests.
+    fn get(&self) -> (u64, u64) {
+        let completed = self.completed.load(Ordering::Relaxed);
+        let started = self.started.load(Ordering::Relaxed);
+        (completed, started - completed)
+    }
+}
+
+#[must_use]
+pub struct Tracked(Arc<Counter>);
+
+impl Tracked {
+    pub fn complete(self) {
+        self.0.completed.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// A response body that completes its request once the last of it has been
+/// handed to the connection, leaving it interrupted if dropped before.
+pub struct TrackedBody<B> {
+    inner: B,
+    tracked: Option<Tracked>,
+}
+
+impl<B: Body> TrackedBody<B> {
+    pub fn new(inner: B, tracked: Tracked) -> Self {
+        let mut body = Self {
+            inner,
+            tracked: Some(tracked),
+        };
+        // Bodies known to be empty may never be polled
+        if body.inner.is_end_stream() {
+            body.complete();
+        }
+        body
+    }
+
+    fn complete(&mut self) {
+        if let Some(tracked) = self.tracked.take() {
+            tracked.complete();
+        }
+    }
+}
+
+impl<B: Body + Unpin> Body for TrackedBody<B> {
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let polled = Pin::new(&mut self.inner).poll_frame(cx);
+        match &polled {
+            Poll::Ready(None) => self.complete(),
+            Poll::Ready(Some(Ok(_))) if self.inner.is_end_stream() => self.complete(),
+            Poll::Ready(Some(_)) | Poll::Pending => {}
+        }
+        polled
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
@@ -0,0 +1,146 @@
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use axum::extract::Query;
+use axum::extract::State;
+use axum::extract::ws::CloseFrame;
+use axum::extract::ws::Message;
+use axum::extract::ws::WebSocket;
+use axum::extract::ws::WebSocketUpgrade;
+use axum::extract::ws::close_code;
+use axum::response::Response;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::debug;
+
+use super::AppState;
+use super::payloads::Source;
+use crate::FrameFormat;
+use crate::Workload;
+use crate::proto;
+use crate::workloads::Rand;
+use crate::workloads::Request;
+use crate::workloads::generate;
+
+#[derive(Deserialize)]
+pub(super) struct Params {
+    #[serde(default)]
+    format: FrameFormat,
+}
+
+/// Upgrade to a WebSocket answering requests for payloads, one frame each,
+/// in the format asked for with `?format=json` or `?format=protobuf`.
+pub(super) async fn upgrade(
+    upgrade: WebSocketUpgrade,
+    Query(Params { format }): Query<Params>,
+    State(state): State<Arc<AppState>>,
+) -> Response {
+    upgrade.on_upgrade(move |socket| serve(socket, format, state))
+}
+
+/// Answer requests on `socket` until the client closes it, closing it
+/// ourselves on requests we can't make sense of.
+async fn serve(mut socket: WebSocket, format: FrameFormat, state: Arc<AppState>) {
+    while let Some(message) = socket.recv().await {
+        let message = match message {
+            Ok(message) => message,
+            Err(error) => {
+                debug!(%error, "receiving frame");
+                return;
+            }
+        };
+        let request = match (format, message) {
+            (FrameFormat::Json, Message::Text(text)) => serde_json::from_str::<Request>(&text)
+                .map_err(|error| (close_code::INVALID, error.to_string())),
+            (FrameFormat::Protobuf, Message::Binary(bytes)) => decode(&bytes),
+            (_, Message::Ping(_) | Message::Pong(_)) => continue,
+            (_, Message::Close(_)) => return,
+            (FrameFormat::Json, Message::Binary(_)) => {
+                Err((close_code::UNSUPPORTED, "expected text frames".to_owned()))
+            }
+            (FrameFormat::Protobuf, Message::Text(_)) => {
+                Err((close_code::UNSUPPORTED, "expected binary frames".to_owned()))
+            }
+        };
+        let request = request.and_then(|request| {
+            state
+                .payloads
+                .check_seed(request.seed.is_some())
+                .map_err(|reason| (close_code::POLICY, reason.to_owned()))?;
+            Ok(request)
+        });
+        let Request { workload, seed } = match request {
+            Ok(request) => request,
+            Err((code, reason)) => {
+                let close = Message::Close(Some(CloseFrame {
+                    code,
+                    reason: Cow::Owned(reason),
+                }));
+                if let Err(error) = socket.send(close).await {
+                    debug!(%error, "closing socket");
+                }
+                return;
+            }
+        };
+        let tracked = state.stats.websocket.track();
+        let payloads = &state.payloads;
+        let response = match workload {
+            Workload::Stringy => respond(&payloads.stringy, seed, format),
+            Workload::Inty => respond(&payloads.inty, seed, format),
+            Workload::Mixed => respond(&payloads.mixed, seed, format),
+        };
+        if let Err(error) = socket.send(response).await {
+            debug!(%error, "sending frame");
+            return;
+        }
+        tracked.complete();
+    }
+}
+
+/// Decode a request sent in a protobuf frame.
+fn decode(bytes: &[u8]) -> Result<Request, (u16, String)> {
+    let request = <proto::WorkloadRequest as prost::Message>::decode(bytes)
+        .map_err(|error| (close_code::INVALID, error.to_string()))?;
+    let workload = match request.workload() {
+        proto::Workload::Stringy => Workload::Stringy,
+        proto::Workload::Inty => Workload::Inty,
+        proto::Workload::Mixed => Workload::Mixed,
+        proto::Workload::Unspecified => {
+            return Err((close_code::INVALID, "missing workload".to_owned()));
+        }
+    };
+    Ok(Request {
+        workload,
+        seed: request.seed,
+    })
+}
+
+/// A frame with the next payload from `source`, like REST responses in JSON
+/// and like gRPC responses in protobuf. A `seed` from the request, which only
+/// random mode accepts, takes precedence.
+fn respond<T, P>(source: &Source<T, P>, seed: Option<u64>, format: FrameFormat) -> Message
+where
+    T: Rand + Serialize + Into<P>,
+    P: prost::Message,
+{
+    match (format, source) {
+        (FrameFormat::Json, Source::Random(seeds)) => {
+            json(&generate::<T>(seed.or_else(|| seeds.next_rest())))
+        }
+        (FrameFormat::Json, Source::Pool(pool)) => json(pool.next_rest()),
+        (FrameFormat::Json, Source::Fixed(fixed)) => Message::Text(fixed.text.clone()),
+        (FrameFormat::Protobuf, Source::Random(seeds)) => Message::Binary(
+            generate::<T>(seed.or_else(|| seeds.next_grpc()))
+                .into()
+                .encode_to_vec(),
+        ),
+        (FrameFormat::Protobuf, Source::Pool(pool)) => {
+            Message::Binary(pool.next_grpc().encode_to_vec())
+        }
+        (FrameFormat::Protobuf, Source::Fixed(fixed)) => Message::Binary(fixed.protobuf.to_vec()),
+    }
+}
+
+fn json(payload: &impl Serialize) -> Message {
+    Message::Text(serde_json::to_string(payload).expect("payloads serialize to JSON"))
+}
@@ -0,0 +1,177 @@
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use anyhow::Context;
+use anyhow::bail;
+use rand::Rng;
+
+/// A string with `{{placeholder}}` substitutions, rendered anew for every
+/// request.
+///
+/// Supported placeholders:
+///
+/// - `{{seq}}`: sequence number of the request, shared by all workers
+/// - `{{int}}`: random 64-bit integer
+/// - `{{int:LOW..HIGH}}`: random integer between `LOW` and `HIGH` (inclusive)
+/// - `{{word}}`: random noun
+/// - `{{words:N}}`: `N` random nouns separated by a space
+/// - `{{uuid}}`: random version 4 UUID
+#[derive(Debug, Clone)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone)]
+enum Part {
+    Literal(String),
+    Seq,
+    Int(RangeInclusive<i64>),
+    Word,
+    Words(usize),
+    Uuid,
+}
+
+impl Template {
+    /// Whether the template renders to the same string every time.
+    pub fn is_constant(&self) -> bool {
+        self.parts
+            .iter()
+            .all(|part| matches!(part, Part::Literal(_)))
+    }
+
+    pub fn render(&self, rng: &mut impl Rng, seq: u64) -> String {
+        let mut rendered = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => rendered.push_str(literal),
+                Part::Seq => rendered.push_str(&seq.to_string()),
+                Part::Int(range) => rendered.push_str(&rng.random_range(range.clone()).to_string()),
+                Part::Word => rendered.push_str(&crate::workloads::word(rng)),
+                Part::Words(n) => rendered.push_str(&crate::workloads::words(rng, *n)),
+                Part::Uuid => rendered.push_str(
+                    &uuid::Builder::from_random_bytes(rng.random())
+                        .into_uuid()
+                        .to_string(),
+                ),
+            }
+        }
+        rendered
+    }
+}
+
+impl FromStr for Template {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut rest = s;
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_string()));
+            }
+            let end = rest[start..]
+                .find("}}")
+                .with_context(|| format!("unclosed placeholder in `{s}`"))?;
+            let placeholder = &rest[start + 2..start + end];
+            parts.push(placeholder.parse()?);
+            rest = &rest[start + end + 2..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+        Ok(Self { parts })
+    }
+}
+
+impl FromStr for Part {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, argument) = match s.trim().split_once(':') {
+            Some((name, argument)) => (name, Some(argument)),
+            None => (s.trim(), None),
+        };
+        let part = match (name, argument) {
+            ("seq", None) => Self::Seq,
+            ("int", None) => Self::Int(i64::MIN..=i64::MAX),
+            ("int", Some(range)) => {
+                let (low, high) = range
+                    .split_once("..")
+                    .with_context(|| format!("expected `LOW..HIGH`, got `{range}`"))?;
+                let low = low.parse().context("parsing lower bound")?;
+                let high = high.parse().context("parsing upper bound")?;
+                if low > high {
+                    bail!("empty range `{range}`");
+                }
+                Self::Int(low..=high)
+            }
+            ("word", None) => Self::Word,
+            ("words", Some(n)) => Self::Words(n.parse().context("parsing number of words")?),
+            ("uuid", None) => Self::Uuid,
+            _ => bail!("unknown placeholder `{{{{{s}}}}}`"),
+        };
+        Ok(part)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::*;
+
+    fn render(template: &str, seq: u64) -> String {
+        template
+            .parse::<Template>()
+            .unwrap()
+            .render(&mut StdRng::seed_from_u64(0), seq)
+    }
+
+    #[test]
+    fn renders_literals_and_seq() {
+        assert_eq!(
+            render("/items/{{seq}}?page={{ seq }}", 7),
+            "/items/7?page=7"
+        );
+        assert_eq!(render("/plain", 7), "/plain");
+        assert_eq!(render("", 7), "");
+    }
+
+    #[test]
+    fn only_literals_are_constant() {
+        assert!("/plain".parse::<Template>().unwrap().is_constant());
+        assert!(!"/{{uuid}}".parse::<Template>().unwrap().is_constant());
+    }
+
+    #[test]
+    fn renders_placeholders_within_their_bounds() {
+        let template: Template = "{{int:-3..3}}".parse().unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..100 {
+            let int: i64 = template.render(&mut rng, 0).parse().unwrap();
+            assert!((-3..=3).contains(&int), "{int}");
+        }
+        assert_eq!(render("{{int:5..5}}", 0), "5");
+        assert_eq!(render("{{words:4}}", 0).split(' ').count(), 4);
+        let uuid = uuid::Uuid::parse_str(&render("{{uuid}}", 0)).unwrap();
+        assert_eq!(uuid.get_version_num(), 4);
+    }
+
+    #[test]
+    fn rejects_malformed_placeholders() {
+        for (template, error) in [
+            ("/{{seq", "unclosed placeholder in `/{{seq`"),
+            ("{{nope}}", "unknown placeholder `{{nope}}`"),
+            ("{{seq:1}}", "unknown placeholder `{{seq:1}}`"),
+            ("{{int:5..1}}", "empty range `5..1`"),
+            ("{{int:1-5}}", "expected `LOW..HIGH`, got `1-5`"),
+            ("{{int:a..5}}", "parsing lower bound"),
+            ("{{int:1..b}}", "parsing upper bound"),
+            ("{{words:many}}", "parsing number of words"),
+        ] {
+            let message = format!("{:#}", template.parse::<Template>().unwrap_err());
+            assert!(message.contains(error), "`{template}`: {message}");
+        }
+    }
+}
@@ -1,6 +1,14 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::BuildHasherDefault;
 use std::ops::Range;
 
 use rand::Rng;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::Workload;
 
 pub mod inty;
 pub mod mixed;
@@ -8,6 +16,73 @@ pub mod stringy;
 
 const COLLECTION_SIZE: Range<usize> = Range { start: 5, end: 15 };
 
+/// Hashes map keys the same in every run, unlike the standard hasher it's
+/// otherwise the same as, so that seeded payloads serialize to the same JSON.
+pub type FixedState = BuildHasherDefault<DefaultHasher>;
+
+/// HTTP header or gRPC metadata key with which a request can ask for the
+/// payload generated from a specific seed.
+pub const SEED_HEADER: &str = "x-battlebots-seed";
+
+/// A request for a payload, sent over a WebSocket in JSON frames.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Request {
+    pub workload: Workload,
+    /// The seed to generate the payload from, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+}
+
+/// A payload that can be generated randomly.
+pub trait Rand {
+    fn rand(rng: &mut impl Rng) -> Self;
+}
+
+/// A payload that can be checked against the invariants every generated
+/// payload upholds.
+pub trait Validate {
+    fn validate(&self) -> Result<(), Invalid>;
+}
+
+/// Why a payload is not one the server could have generated.
+#[derive(Debug, thiserror::Error)]
+pub enum Invalid {
+    #[error("`{0}` is missing")]
+    Missing(&'static str),
+    #[error("`{field}` has {len} elements, expected {COLLECTION_SIZE:?}")]
+    Size { field: &'static str, len: usize },
+    #[error("payload differs from the one generated from seed {0}")]
+    Mismatch(u64),
+}
+
+/// Check that a collection has a size [`COLLECTION_SIZE`] allows.
+///
+/// Maps are only checked to be non-empty and not too large, as keys drawn at
+/// random may collide.
+fn check_size(field: &'static str, len: usize, map: bool) -> Result<(), Invalid> {
+    let valid = if map {
+        len > 0 && len < COLLECTION_SIZE.end
+    } else {
+        COLLECTION_SIZE.contains(&len)
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(Invalid::Size { field, len })
+    }
+}
+
+/// Generate a payload from `seed`, or from the thread-local generator if
+/// there is none.
+///
+/// The same seed always results in the same payload.
+pub fn generate<T: Rand>(seed: Option<u64>) -> T {
+    match seed {
+        Some(seed) => T::rand(&mut StdRng::seed_from_u64(seed)),
+        None => T::rand(&mut rand::rng()),
+    }
+}
+
 /// Generate a random string with `n` amount of words, separated by a space.
 pub fn words(rng: &mut impl Rng, n: usize) -> String {
     use rand::seq::IndexedRandom;
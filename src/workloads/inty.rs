@@ -4,19 +4,28 @@ use rand::Rng;
 use serde::Deserialize;
 use serde::Serialize;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Payload {
     pub header: String,
-    pub configuration: HashMap<String, i64>,
+    pub configuration: HashMap<String, i64, super::FixedState>,
     pub ids: Vec<i64>,
 }
 
-impl Payload {
-    pub fn rand(rng: &mut impl Rng) -> Self {
+impl super::Validate for Payload {
+    fn validate(&self) -> Result<(), super::Invalid> {
+        super::check_size("header", self.header.split(' ').count(), false)?;
+        super::check_size("configuration", self.configuration.len(), true)?;
+        super::check_size("ids", self.ids.len(), false)
+    }
+}
+
+impl super::Rand for Payload {
+    fn rand(rng: &mut impl Rng) -> Self {
         let n_words = rng.random_range(super::COLLECTION_SIZE);
         let header = super::words(rng, n_words);
         let n_configs = rng.random_range(super::COLLECTION_SIZE);
-        let mut configuration = HashMap::with_capacity(n_configs);
+        let mut configuration =
+            HashMap::with_capacity_and_hasher(n_configs, super::FixedState::default());
         for _ in 0..n_configs {
             configuration.insert(super::word(rng), rng.random());
         }
@@ -2,14 +2,25 @@ use rand::Rng;
 use serde::Deserialize;
 use serde::Serialize;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+use super::Invalid;
+use super::Rand;
+use super::Validate;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Payload {
     pub stringy: super::stringy::Payload,
     pub inty: super::inty::Payload,
 }
 
-impl Payload {
-    pub fn rand(rng: &mut impl Rng) -> Self {
+impl Validate for Payload {
+    fn validate(&self) -> Result<(), Invalid> {
+        self.stringy.validate()?;
+        self.inty.validate()
+    }
+}
+
+impl Rand for Payload {
+    fn rand(rng: &mut impl Rng) -> Self {
         Self {
             stringy: super::stringy::Payload::rand(rng),
             inty: super::inty::Payload::rand(rng),
@@ -4,19 +4,32 @@ use rand::Rng;
 use serde::Deserialize;
 use serde::Serialize;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Payload {
     pub body: String,
     pub messages: Vec<String>,
-    pub configuration: HashMap<String, String>,
+    pub configuration: HashMap<String, String, super::FixedState>,
 }
 
-impl Payload {
-    pub fn rand(rng: &mut impl Rng) -> Self {
+impl super::Validate for Payload {
+    fn validate(&self) -> Result<(), super::Invalid> {
+        super::check_size("body", self.body.split(' ').count(), false)?;
+        super::check_size("configuration", self.configuration.len(), true)?;
+        super::check_size("messages", self.messages.len(), false)?;
+        for message in &self.messages {
+            super::check_size("messages[]", message.split(' ').count(), false)?;
+        }
+        Ok(())
+    }
+}
+
+impl super::Rand for Payload {
+    fn rand(rng: &mut impl Rng) -> Self {
         let n_words = rng.random_range(super::COLLECTION_SIZE);
         let body = super::words(rng, n_words);
         let n_configs = rng.random_range(super::COLLECTION_SIZE);
-        let mut configuration = HashMap::with_capacity(n_configs);
+        let mut configuration =
+            HashMap::with_capacity_and_hasher(n_configs, super::FixedState::default());
         for _ in 0..n_configs {
             configuration.insert(super::word(rng), super::word(rng));
         }